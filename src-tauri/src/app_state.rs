@@ -1,83 +1,148 @@
-use std::{
-    collections::HashMap,
-    fs, sync::{Arc},
-};
-use tauri::{Manager, Runtime, State, async_runtime::Sender};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
 use tokio::sync::Mutex;
 
-use crate::config::{self, Config};
+use crate::auth::AuthStore;
+use crate::config::Config;
+use crate::engine::DownloadEngine;
+use crate::error::VscraperError;
+use crate::history::HistoryStore;
+use crate::install_source::InstallSources;
+use crate::mitigation::MitigationSettings;
+use crate::network::NetworkSettings;
+use crate::queue::DownloadQueue;
+use crate::retry::RetryPolicy;
+use crate::versions::YtdlpUpdateSettings;
 
 pub struct AppState {
+    auth: AuthStore,
     config: Config,
-    current_downloads: HashMap<String, Sender<()>>,
+    /// Directory `DownloadOptions::name_format` is resolved against.
+    download_dir: PathBuf,
+    history: HistoryStore,
+    queue: DownloadQueue,
 }
 
 impl AppState {
-    pub async fn init<R: Runtime>(
-        app_handle: tauri::AppHandle<R>,
-    ) -> Result<AppState, Box<dyn std::error::Error>> {
+    pub fn init(
+        config_dir: &Path,
+        data_dir: &Path,
+        download_dir: PathBuf,
+        queue: DownloadQueue,
+    ) -> Result<AppState, VscraperError> {
+        let config = Config::load(config_dir)?;
+        fs::create_dir_all(data_dir)?;
+        let history = HistoryStore::open(&config.get_history_path(data_dir))?;
         Ok(AppState {
-            config: Self::handle_config(app_handle),
-            current_downloads: HashMap::new(),
+            auth: AuthStore::load(config_dir, data_dir)?,
+            config,
+            download_dir,
+            history,
+            queue,
         })
     }
-    
+
     pub fn get_config(&self) -> Config {
         self.config.clone()
     }
 
+    pub fn download_dir(&self) -> &Path {
+        &self.download_dir
+    }
+
     pub fn set_skip_homepage(&mut self, new_preference: bool) {
         self.config.set_skip_homepage(new_preference);
     }
 
-    pub fn add_download(&mut self, url: String, sender: Sender<()>) -> bool {
-        match self.current_downloads.contains_key(&url) {
-            true => false,
-            false => {
-                self.current_downloads.insert(url, sender);
-                true
-            },
-        }
-    }
-
-    pub fn get_download(&self, url: &str) -> Option<&Sender<()>> {
-        self.current_downloads.get(url)
-    }
-
-    fn handle_config<R: Runtime>(app_handle: tauri::AppHandle<R>) -> Config {
-        let dir = app_handle.path().app_config_dir();
-        let user_config = match dir {
-            Ok(dir) => match fs::create_dir_all(&dir) {
-                Ok(_) => {
-                    let file = dir.join(config::CONFIG_FILENAME);
-                    let file_data = fs::read(file);
-                    match file_data {
-                        Ok(file_data) => serde_json::from_slice(&file_data).unwrap(),
-                        Err(err) => match err.kind() {
-                            std::io::ErrorKind::NotFound => Config::default(),
-                            err => todo!("unknown potential errors: {}", err),
-                        },
-                    }
-                }
-                Err(err) => todo!("unable to get create directory: {}", err),
-            },
-            Err(err) => {
-                // Handle config directory error - should be panic?
-                todo!("unable to get config directory: {}", err)
-                // panic!("unable to get config directory: {}", err)
-            }
-        };
-        user_config
+    pub fn set_max_parallel_downloads(&mut self, max_parallel_downloads: usize) {
+        self.config.set_max_parallel_downloads(max_parallel_downloads);
+    }
+
+    pub fn set_mitigation(&mut self, mitigation: MitigationSettings) {
+        self.config.set_mitigation(mitigation);
+    }
+
+    pub fn set_network(&mut self, network: NetworkSettings) {
+        self.config.set_network(network);
+    }
+
+    pub fn set_retry(&mut self, retry: RetryPolicy) {
+        self.config.set_retry(retry);
+    }
+
+    pub fn set_ytdlp_updates(&mut self, ytdlp_updates: YtdlpUpdateSettings) {
+        self.config.set_ytdlp_updates(ytdlp_updates);
+    }
+
+    pub fn set_install_sources(&mut self, install_sources: InstallSources) {
+        self.config.set_install_sources(install_sources);
+    }
+
+    pub fn set_use_system_binaries(&mut self, use_system_binaries: bool) {
+        self.config.set_use_system_binaries(use_system_binaries);
+    }
+
+    pub fn auth(&self) -> &AuthStore {
+        &self.auth
+    }
+
+    pub fn auth_mut(&mut self) -> &mut AuthStore {
+        &mut self.auth
+    }
+
+    pub fn history(&self) -> &HistoryStore {
+        &self.history
+    }
+
+    pub fn queue(&self) -> &DownloadQueue {
+        &self.queue
+    }
+
+    pub fn queue_mut(&mut self) -> &mut DownloadQueue {
+        &mut self.queue
     }
 }
 
 #[tauri::command]
-pub async fn get_config(state: State<'_, Arc<Mutex<AppState>>>) -> tauri::Result<Config> {
+pub async fn get_config(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Config, VscraperError> {
     let state = state.lock().await;
     Ok(state.get_config())
 }
 
 #[tauri::command]
-pub async fn update_skip_homepage(state: State<'_, Arc<Mutex<AppState>>>, updated_preference: bool) -> tauri::Result<()> {
-    Ok(state.lock().await.set_skip_homepage(updated_preference))
+pub async fn update_skip_homepage(state: State<'_, Arc<Mutex<AppState>>>, updated_preference: bool) -> Result<(), VscraperError> {
+    state.lock().await.set_skip_homepage(updated_preference);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn update_max_parallel_downloads(
+    engine: State<'_, DownloadEngine>,
+    max_parallel_downloads: usize,
+) -> Result<(), VscraperError> {
+    engine.state().lock().await.set_max_parallel_downloads(max_parallel_downloads);
+    // Raising the limit may allow queued jobs to start right away.
+    engine.schedule();
+    Ok(())
+}
+
+/// Replaces the mitigation profiles and their domain defaults, jobs already running keep
+/// the profile they started with.
+#[tauri::command]
+pub async fn update_mitigation_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    mitigation: MitigationSettings,
+) -> Result<(), VscraperError> {
+    mitigation.validate()?;
+    state.lock().await.set_mitigation(mitigation);
+    Ok(())
+}
+
+/// Replaces the retry policy of jobs that do not bring their own.
+#[tauri::command]
+pub async fn update_retry_policy(state: State<'_, Arc<Mutex<AppState>>>, retry: RetryPolicy) -> Result<(), VscraperError> {
+    state.lock().await.set_retry(retry);
+    Ok(())
 }
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+/// Codecs yt-dlp can extract audio to with ffmpeg.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Mp3,
+    M4a,
+    Opus,
+    Flac,
+    Wav,
+}
+
+impl AudioCodec {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AudioCodec::Mp3 => "mp3",
+            AudioCodec::M4a => "m4a",
+            AudioCodec::Opus => "opus",
+            AudioCodec::Flac => "flac",
+            AudioCodec::Wav => "wav",
+        }
+    }
+}
+
+/// Audio only mode, the video stream is dropped after download.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AudioOptions {
+    codec: AudioCodec,
+    /// Either a VBR quality from `0` (best) to `10` (worst) or a bitrate such as `192K`.
+    #[serde(default = "default_audio_quality")]
+    quality: String,
+    #[serde(default)]
+    embed_thumbnail: bool,
+    #[serde(default)]
+    embed_metadata: bool,
+}
+
+fn default_audio_quality() -> String {
+    String::from("5")
+}
+
+impl AudioOptions {
+    pub fn new(codec: AudioCodec, quality: String, embed_thumbnail: bool, embed_metadata: bool) -> AudioOptions {
+        AudioOptions {
+            codec,
+            quality,
+            embed_thumbnail,
+            embed_metadata,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let quality_is_valid = match self.quality.strip_suffix(['K', 'k']) {
+            Some(bitrate) => bitrate.parse::<u32>().is_ok_and(|bitrate| bitrate > 0),
+            None => self.quality.parse::<u8>().is_ok_and(|quality| quality <= 10),
+        };
+        if !quality_is_valid {
+            return Err(format!(
+                "invalid audio quality {}, expected 0-10 or a bitrate such as 192K",
+                self.quality
+            ));
+        }
+
+        if self.codec == AudioCodec::Wav && (self.embed_thumbnail || self.embed_metadata) {
+            return Err(String::from("wav files cannot hold cover art or metadata"));
+        }
+        Ok(())
+    }
+
+    /// Arguments for yt-dlp's extract audio postprocessing.
+    pub fn ytdlp_args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("--extract-audio"),
+            String::from("--audio-format"),
+            String::from(self.codec.as_str()),
+            String::from("--audio-quality"),
+            self.quality.clone(),
+        ];
+        if self.embed_thumbnail {
+            args.push(String::from("--embed-thumbnail"));
+        }
+        if self.embed_metadata {
+            args.push(String::from("--embed-metadata"));
+        }
+        args
+    }
+}
+
+#[test]
+fn test_audio_options_validate() {
+    let options = |json: serde_json::Value| -> AudioOptions { serde_json::from_value(json).unwrap() };
+
+    assert!(options(serde_json::json!({ "codec": "mp3" })).validate().is_ok());
+    assert!(options(serde_json::json!({ "codec": "opus", "quality": "160K" })).validate().is_ok());
+    assert!(options(serde_json::json!({ "codec": "mp3", "quality": "11" })).validate().is_err());
+    assert!(options(serde_json::json!({ "codec": "m4a", "quality": "fast" })).validate().is_err());
+    assert!(options(serde_json::json!({ "codec": "wav", "embed_thumbnail": true })).validate().is_err());
+}
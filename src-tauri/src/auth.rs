@@ -0,0 +1,515 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use tauri::State;
+use tauri_plugin_log::log::error;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::error::VscraperError;
+use crate::queue::JobId;
+use crate::ytdlp;
+
+pub const AUTH_FILENAME: &str = "auth.json";
+const AUTH_BACKUP_FILENAME: &str = "auth.json.bak";
+/// OS keyring entry holding the key the secrets in `auth.json` are encrypted with, so a
+/// copy of the config directory alone does not reveal them.
+const KEYRING_SERVICE: &str = "vscraper";
+const KEYRING_KEY_USER: &str = "auth-key";
+/// Where earlier versions kept the key, moved into the keyring on first use.
+const LEGACY_KEY_FILENAME: &str = "auth.key";
+/// Directory under the app data directory holding the secrets decrypted for running
+/// jobs, only the current user can open it.
+const SECRETS_DIRNAME: &str = "secrets";
+/// Prefix of every file in [`SECRETS_DIRNAME`].
+const SECRET_FILE_PREFIX: &str = "vscraper-";
+
+/// An encrypted value, both fields base64.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Secret {
+    nonce: String,
+    ciphertext: String,
+}
+
+/// How yt-dlp authenticates with a site, as stored on disk.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StoredAuth {
+    /// Contents of an imported cookies.txt.
+    Cookies { cookies: Secret },
+    Password { username: String, password: Secret },
+    /// A netrc entry, `machine` is the name of the yt-dlp extractor, such as `youtube`.
+    Netrc { machine: String, login: String, password: Secret },
+    /// Cookies read from a local browser, `profile` is a profile name or directory.
+    Browser { browser: String, profile: Option<String> },
+}
+
+/// Authentication as entered in the frontend, secrets are encrypted before they are stored.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthInput {
+    /// Imports the cookies.txt at `path`, the file itself is not needed afterwards.
+    CookiesFile { path: PathBuf },
+    Password { username: String, password: String },
+    Netrc { machine: String, login: String, password: String },
+    Browser { browser: String, profile: Option<String> },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AuthProfile {
+    /// Domain the profile applies to, subdomains included.
+    site: String,
+    #[serde(flatten)]
+    auth: StoredAuth,
+}
+
+/// What the frontend gets to see of a profile, never its secrets.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct AuthProfileSummary {
+    site: String,
+    method: &'static str,
+    /// Username, netrc login or browser, depending on `method`.
+    account: Option<String>,
+}
+
+/// yt-dlp arguments authenticating one job. Files holding decrypted secrets only live
+/// as long as this does, or until the next [`AuthStore::load`] if the app crashes.
+#[derive(Debug, Default)]
+pub struct JobAuth {
+    args: Vec<OsString>,
+    files: Vec<PathBuf>,
+}
+
+impl JobAuth {
+    pub fn args(&self) -> &[OsString] {
+        &self.args
+    }
+}
+
+impl Drop for JobAuth {
+    fn drop(&mut self) {
+        for file in &self.files {
+            if let Err(err) = fs::remove_file(file) {
+                error!("removing auth file: {}, err: {}", file.display(), err);
+            }
+        }
+    }
+}
+
+/// Per-site authentication profiles, saved to `auth.json` next to `settings.json`.
+pub struct AuthStore {
+    dir: PathBuf,
+    /// Where jobs' decrypted secrets are written, see [`SECRETS_DIRNAME`].
+    secrets_dir: PathBuf,
+    /// Set once a secret is first sealed or opened, so the keyring is only unlocked
+    /// when it is needed.
+    cipher: OnceLock<Aes256Gcm>,
+    profiles: Vec<AuthProfile>,
+}
+
+impl AuthStore {
+    /// Loads the profiles in `dir`. Profiles that fail to parse are moved to
+    /// `auth.json.bak`, the store starts out empty instead. Secrets a crashed run left
+    /// in `data_dir` are removed.
+    pub fn load(dir: &Path, data_dir: &Path) -> Result<AuthStore, VscraperError> {
+        fs::create_dir_all(dir)?;
+        let secrets_dir = data_dir.join(SECRETS_DIRNAME);
+        create_private_dir(&secrets_dir)?;
+        remove_leftover_secrets(&secrets_dir);
+
+        let file = dir.join(AUTH_FILENAME);
+        let profiles = match fs::read(&file) {
+            Ok(file_data) => match serde_json::from_slice(&file_data) {
+                Ok(profiles) => profiles,
+                Err(err) => {
+                    let backup = dir.join(AUTH_BACKUP_FILENAME);
+                    error!("corrupt {}: {}, backing up to {}", AUTH_FILENAME, err, backup.display());
+                    fs::rename(&file, &backup)?;
+                    Vec::new()
+                }
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(AuthStore {
+            dir: dir.to_path_buf(),
+            secrets_dir,
+            cipher: OnceLock::new(),
+            profiles,
+        })
+    }
+
+    fn save(&self) -> Result<(), VscraperError> {
+        write_private(&self.dir.join(AUTH_FILENAME), &serde_json::to_vec_pretty(&self.profiles)?)?;
+        Ok(())
+    }
+
+    pub fn profiles(&self) -> Vec<AuthProfileSummary> {
+        self.profiles.iter().map(AuthProfile::summary).collect()
+    }
+
+    /// Adds or replaces the profile for `site` and saves the store.
+    pub fn set(&mut self, site: &str, input: AuthInput) -> Result<(), VscraperError> {
+        let site = normalize_site(site);
+        if site.is_empty() {
+            return Err(VscraperError::Auth(String::from("site must not be empty")));
+        }
+        let auth = match input {
+            AuthInput::CookiesFile { path } => StoredAuth::Cookies {
+                cookies: self.seal(&fs::read(&path)?)?,
+            },
+            AuthInput::Password { username, password } => StoredAuth::Password {
+                username,
+                password: self.seal(password.as_bytes())?,
+            },
+            AuthInput::Netrc { machine, login, password } => StoredAuth::Netrc {
+                machine,
+                login,
+                password: self.seal(password.as_bytes())?,
+            },
+            AuthInput::Browser { browser, profile } => StoredAuth::Browser { browser, profile },
+        };
+
+        self.profiles.retain(|profile| profile.site != site);
+        self.profiles.push(AuthProfile { site, auth });
+        self.save()
+    }
+
+    /// Removes the profile for `site`, returns false if there was none.
+    pub fn remove(&mut self, site: &str) -> Result<bool, VscraperError> {
+        let site = normalize_site(site);
+        let count = self.profiles.len();
+        self.profiles.retain(|profile| profile.site != site);
+        match self.profiles.len() == count {
+            true => Ok(false),
+            false => self.save().map(|_| true),
+        }
+    }
+
+    /// Arguments authenticating a probe or playlist listing of `url`, which belongs to no
+    /// job. Its files are named after an id of its own.
+    pub fn probe_auth(&self, url: &str) -> Result<JobAuth, VscraperError> {
+        self.job_auth(&JobId::new(), url)
+    }
+
+    /// Arguments authenticating the download of `url` with the most specific matching
+    /// profile. Secrets yt-dlp can only read from files are decrypted into `job_id`'s own
+    /// files in the secrets directory.
+    pub fn job_auth(&self, job_id: &JobId, url: &str) -> Result<JobAuth, VscraperError> {
+        let Some(host) = ytdlp::url_host(url) else {
+            return Ok(JobAuth::default());
+        };
+        let profile = self
+            .profiles
+            .iter()
+            .filter(|profile| ytdlp::host_matches(&host, &profile.site))
+            .max_by_key(|profile| profile.site.len());
+
+        let mut job_auth = JobAuth::default();
+        match profile.map(|profile| &profile.auth) {
+            None => {}
+            Some(StoredAuth::Cookies { cookies }) => {
+                let path = self.secret_path(job_id, "cookies.txt");
+                write_private(&path, &self.open(cookies)?)?;
+                job_auth.args.extend([OsString::from("--cookies"), path.clone().into_os_string()]);
+                job_auth.files.push(path);
+            }
+            Some(StoredAuth::Password { username, password }) => {
+                // Arguments are visible to every user in the process list, a config file is not.
+                let password = String::from_utf8_lossy(&self.open(password)?).into_owned();
+                let path = self.secret_path(job_id, "conf");
+                let config = format!("--username {}\n--password {}\n", config_quote(username), config_quote(&password));
+                write_private(&path, config.as_bytes())?;
+                job_auth.args.extend([OsString::from("--config-location"), path.clone().into_os_string()]);
+                job_auth.files.push(path);
+            }
+            Some(StoredAuth::Netrc { machine, login, password }) => {
+                let password = String::from_utf8_lossy(&self.open(password)?).into_owned();
+                let path = self.secret_path(job_id, "netrc");
+                let entry = format!(
+                    "machine {} login {} password {}\n",
+                    netrc_quote(machine),
+                    netrc_quote(login),
+                    netrc_quote(&password)
+                );
+                write_private(&path, entry.as_bytes())?;
+                job_auth.args.extend([
+                    OsString::from("--netrc"),
+                    OsString::from("--netrc-location"),
+                    path.clone().into_os_string(),
+                ]);
+                job_auth.files.push(path);
+            }
+            Some(StoredAuth::Browser { browser, profile }) => {
+                let source = match profile {
+                    Some(profile) => format!("{}:{}", browser, profile),
+                    None => browser.clone(),
+                };
+                job_auth.args.extend([OsString::from("--cookies-from-browser"), OsString::from(source)]);
+            }
+        }
+        Ok(job_auth)
+    }
+
+    fn secret_path(&self, job_id: &JobId, extension: &str) -> PathBuf {
+        self.secrets_dir.join(format!("{}{}.{}", SECRET_FILE_PREFIX, job_id, extension))
+    }
+
+    /// The cipher for secrets, with the key from the OS keyring.
+    fn cipher(&self) -> Result<&Aes256Gcm, VscraperError> {
+        if let Some(cipher) = self.cipher.get() {
+            return Ok(cipher);
+        }
+        let key = load_key(&self.dir)?;
+        Ok(self.cipher.get_or_init(|| Aes256Gcm::new(&key)))
+    }
+
+    fn seal(&self, plaintext: &[u8]) -> Result<Secret, VscraperError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, plaintext)
+            .map_err(|err| VscraperError::Auth(format!("encrypting secret: {}", err)))?;
+        Ok(Secret {
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        })
+    }
+
+    fn open(&self, secret: &Secret) -> Result<Vec<u8>, VscraperError> {
+        let decode = |value: &str| {
+            BASE64
+                .decode(value)
+                .map_err(|err| VscraperError::Auth(format!("decoding secret: {}", err)))
+        };
+        let nonce = decode(&secret.nonce)?;
+        if nonce.len() != 12 {
+            return Err(VscraperError::Auth(String::from("invalid secret nonce")));
+        }
+        self.cipher()?
+            .decrypt(Nonce::from_slice(&nonce), decode(&secret.ciphertext)?.as_slice())
+            .map_err(|_| VscraperError::Auth(String::from("decrypting secret, was the key in the OS keyring replaced?")))
+    }
+}
+
+impl AuthProfile {
+    fn summary(&self) -> AuthProfileSummary {
+        let (method, account) = match &self.auth {
+            StoredAuth::Cookies { .. } => ("cookies", None),
+            StoredAuth::Password { username, .. } => ("password", Some(username.clone())),
+            StoredAuth::Netrc { login, .. } => ("netrc", Some(login.clone())),
+            StoredAuth::Browser { browser, .. } => ("browser", Some(browser.clone())),
+        };
+        AuthProfileSummary {
+            site: self.site.clone(),
+            method,
+            account,
+        }
+    }
+}
+
+/// Reads the key from the OS keyring, creating it on first use. A key file left in `dir`
+/// by an earlier version is moved into the keyring instead.
+fn load_key(dir: &Path) -> Result<Key<Aes256Gcm>, VscraperError> {
+    let keyring_error = |err: keyring::Error| VscraperError::Auth(format!("OS keyring: {}", err));
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_KEY_USER).map_err(keyring_error)?;
+    let key = match entry.get_password() {
+        Ok(encoded) => BASE64
+            .decode(encoded)
+            .map_err(|err| VscraperError::Auth(format!("decoding key: {}", err)))?,
+        Err(keyring::Error::NoEntry) => {
+            let legacy_path = dir.join(LEGACY_KEY_FILENAME);
+            let key = match fs::read(&legacy_path) {
+                Ok(key) => key,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Aes256Gcm::generate_key(OsRng).to_vec(),
+                Err(err) => return Err(err.into()),
+            };
+            entry.set_password(&BASE64.encode(&key)).map_err(keyring_error)?;
+            if legacy_path.exists() {
+                fs::remove_file(&legacy_path)?;
+            }
+            key
+        }
+        Err(err) => return Err(keyring_error(err)),
+    };
+    match key.len() {
+        32 => Ok(*Key::<Aes256Gcm>::from_slice(&key)),
+        _ => Err(VscraperError::Auth(String::from("the auth key is not a 256 bit key"))),
+    }
+}
+
+/// The domain profiles are stored under, `YouTube.com` and `www.youtube.com` are the same site.
+fn normalize_site(site: &str) -> String {
+    site.trim().to_lowercase().trim_start_matches("www.").to_string()
+}
+
+/// Quotes `value` for a yt-dlp config file, which is split like a shell command line.
+fn config_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Quotes a netrc token the same way, but only when it has to: Python before 3.11 reads
+/// quotes literally, while 3.11 and later split unquoted tokens on whitespace.
+fn netrc_quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && !value.starts_with('#')
+        && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\');
+    match is_plain {
+        true => value.to_string(),
+        false => config_quote(value),
+    }
+}
+
+/// Creates `dir` so only the current user can open it, tightening it if it exists.
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)?;
+    #[cfg(unix)]
+    fs::set_permissions(dir, std::os::unix::fs::PermissionsExt::from_mode(0o700))?;
+    Ok(())
+}
+
+/// Removes the secrets files of jobs that never got to drop their [`JobAuth`].
+fn remove_leftover_secrets(secrets_dir: &Path) {
+    let entries = match fs::read_dir(secrets_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("reading {}: {}", secrets_dir.display(), err);
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(SECRET_FILE_PREFIX) {
+            if let Err(err) = fs::remove_file(entry.path()) {
+                error!("removing leftover auth file: {}, err: {}", entry.path().display(), err);
+            }
+        }
+    }
+}
+
+/// Writes a file only the current user can read.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, contents)
+}
+
+#[tauri::command]
+pub async fn list_auth_profiles(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<AuthProfileSummary>, VscraperError> {
+    Ok(state.lock().await.auth().profiles())
+}
+
+#[tauri::command]
+pub async fn set_auth_profile(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    site: String,
+    auth: AuthInput,
+) -> Result<(), VscraperError> {
+    state.lock().await.auth_mut().set(&site, auth)
+}
+
+#[tauri::command]
+pub async fn remove_auth_profile(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    site: String,
+) -> Result<bool, VscraperError> {
+    state.lock().await.auth_mut().remove(&site)
+}
+
+#[test]
+fn test_auth_profiles_are_encrypted() {
+    let dir = crate::testing::TempDir::new("auth");
+    // A fixed key keeps the test away from the user's keyring.
+    let key = Aes256Gcm::generate_key(OsRng);
+    let mut store = AuthStore::load(&dir, &dir).unwrap();
+    assert!(store.cipher.set(Aes256Gcm::new(&key)).is_ok());
+    store
+        .set(
+            "Vimeo.com",
+            AuthInput::Password {
+                username: String::from("user"),
+                password: String::from("hunter2"),
+            },
+        )
+        .unwrap();
+    store
+        .set(
+            "music.youtube.com",
+            AuthInput::Browser {
+                browser: String::from("firefox"),
+                profile: None,
+            },
+        )
+        .unwrap();
+    assert!(!fs::read_to_string(dir.join(AUTH_FILENAME)).unwrap().contains("hunter2"));
+
+    let store = AuthStore::load(&dir, &dir).unwrap();
+    assert!(store.cipher.set(Aes256Gcm::new(&key)).is_ok());
+    let job_id = JobId::new();
+    let args = |url: &str| -> Vec<OsString> { store.job_auth(&job_id, url).unwrap().args().to_vec() };
+    let job_auth = store.job_auth(&job_id, "https://player.vimeo.com/video/1").unwrap();
+    assert_eq!(job_auth.args()[0], "--config-location");
+    let config_path = PathBuf::from(&job_auth.args()[1]);
+    assert_eq!(config_path.parent().unwrap(), dir.join(SECRETS_DIRNAME));
+    assert_eq!(fs::read_to_string(&config_path).unwrap(), "--username \"user\"\n--password \"hunter2\"\n");
+    drop(job_auth);
+    assert!(!config_path.exists());
+    assert_eq!(args("https://music.youtube.com/watch?v=1"), ["--cookies-from-browser", "firefox"]);
+    assert!(args("https://www.youtube.com/watch?v=1").is_empty());
+    assert!(args("https://notvimeo.com/1").is_empty());
+}
+
+#[test]
+fn test_auth_store_recovers_and_removes_by_site() {
+    let dir = crate::testing::TempDir::new("auth-remove");
+    fs::write(dir.join(AUTH_FILENAME), "[{ not json").unwrap();
+    // Secrets of a job that was running when the app was killed.
+    let leftover = dir.join(SECRETS_DIRNAME).join(format!("{}{}.netrc", SECRET_FILE_PREFIX, JobId::new()));
+    fs::create_dir_all(leftover.parent().unwrap()).unwrap();
+    fs::write(&leftover, "machine vimeo.com login user password hunter2\n").unwrap();
+
+    let mut store = AuthStore::load(&dir, &dir).unwrap();
+    assert!(store.profiles().is_empty());
+    assert_eq!(fs::read_to_string(dir.join(AUTH_BACKUP_FILENAME)).unwrap(), "[{ not json");
+    assert!(!leftover.exists());
+
+    let browser = AuthInput::Browser {
+        browser: String::from("firefox"),
+        profile: None,
+    };
+    store.set("www.YouTube.com", browser).unwrap();
+    assert!(store.remove("YouTube.com").unwrap());
+    assert!(!store.remove("www.youtube.com").unwrap());
+}
+
+#[test]
+fn test_netrc_fields_are_quoted() {
+    let dir = crate::testing::TempDir::new("auth-netrc");
+    let mut store = AuthStore::load(&dir, &dir).unwrap();
+    assert!(store.cipher.set(Aes256Gcm::new(&Aes256Gcm::generate_key(OsRng))).is_ok());
+    let netrc = AuthInput::Netrc {
+        machine: String::from("vimeo.com"),
+        login: String::from("user"),
+        password: String::from("correct horse #\"battery\""),
+    };
+    store.set("vimeo.com", netrc).unwrap();
+
+    let job_auth = store.job_auth(&JobId::new(), "https://vimeo.com/1").unwrap();
+    assert_eq!(
+        fs::read_to_string(&job_auth.args()[2]).unwrap(),
+        concat!(r#"machine vimeo.com login user password "correct horse #\"battery\"""#, "\n")
+    );
+}
@@ -0,0 +1,76 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use ts_rs::TS;
+
+/// Checksum file yt-dlp publishes with every release, in `sha256sum` format.
+pub const SHA256SUMS_ASSET: &str = "SHA2-256SUMS";
+
+/// Suffix of the per-asset checksum files some projects publish next to each asset.
+pub const SHA256_SUFFIX: &str = ".sha256";
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Hashes by file name from `sha256sum` output, `<hash>  <name>` or `<hash> *<name>` per line.
+pub fn parse_sums(sums: &str) -> HashMap<String, String> {
+    sums.lines()
+        .filter_map(|line| line.trim().split_once(char::is_whitespace))
+        .map(|(hash, name)| (name.trim_start().trim_start_matches('*').to_string(), hash.to_lowercase()))
+        .collect()
+}
+
+/// The hash at the start of a per-asset checksum file, or of a GitHub asset digest
+/// such as `sha256:<hash>`.
+pub fn parse_hash(hash: &str) -> Option<String> {
+    let hash = hash.split_whitespace().next()?;
+    let hash = hash.strip_prefix("sha256:").unwrap_or(hash);
+    (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())).then(|| hash.to_lowercase())
+}
+
+/// A downloaded asset compared to the hash its release published.
+#[derive(Clone, Debug, Serialize, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct ChecksumVerification {
+    asset: String,
+    expected: String,
+    actual: String,
+    verified: bool,
+}
+
+impl ChecksumVerification {
+    pub fn new(asset: &str, expected: String, data: &[u8]) -> ChecksumVerification {
+        let actual = sha256_hex(data);
+        ChecksumVerification {
+            asset: asset.to_string(),
+            verified: actual == expected,
+            expected,
+            actual,
+        }
+    }
+
+    pub fn verified(&self) -> bool {
+        self.verified
+    }
+}
+
+impl std::fmt::Display for ChecksumVerification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: expected sha256 {}, got {}", self.asset, self.expected, self.actual)
+    }
+}
+
+#[test]
+fn test_checksum_verification() {
+    let data = b"yt-dlp";
+    let hash = sha256_hex(data);
+    let sums = parse_sums(&format!("{}  yt-dlp_linux\n{} *yt-dlp.exe\n", hash, "0".repeat(64)));
+    assert_eq!(sums.get("yt-dlp_linux"), Some(&hash));
+    assert!(ChecksumVerification::new("yt-dlp_linux", sums["yt-dlp_linux"].clone(), data).verified());
+    assert!(!ChecksumVerification::new("yt-dlp.exe", sums["yt-dlp.exe"].clone(), data).verified());
+
+    assert_eq!(parse_hash(&format!("sha256:{}", hash.to_uppercase())), Some(hash.clone()));
+    assert_eq!(parse_hash(&format!("{}  ffmpeg-linux-x64\n", hash)), Some(hash));
+    assert_eq!(parse_hash("sha256:abc"), None);
+}
@@ -0,0 +1,505 @@
+use clap::{Args, Subcommand};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri_plugin_log::log::{self, LevelFilter, Log, Metadata, Record};
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::audio::{AudioCodec, AudioOptions};
+use crate::auth::AuthStore;
+use crate::components;
+use crate::config::Config;
+use crate::container::Container;
+use crate::dependencies::{self, Dependency};
+use crate::emissions::Emission;
+use crate::error::VscraperError;
+use crate::engine::DownloadEngine;
+use crate::events::{ChannelSink, EventSink, LoggingSink};
+use crate::installs::InstallTask;
+use crate::probe;
+use crate::queue::{DownloadQueue, JobId, JobState};
+use crate::retry::RetryPolicy;
+use crate::versions;
+use crate::ytdlp::{self, DownloadOptions};
+
+/// Same directory name Tauri derives `app_config_dir` and `app_data_dir` from, so the CLI
+/// and the GUI share their settings, queue and history.
+const APP_IDENTIFIER: &str = "vscraper";
+
+const EXIT_SUCCESS: i32 = 0;
+/// At least one download failed or was cancelled.
+const EXIT_DOWNLOAD_FAILED: i32 = 1;
+/// Any other error, such as a failed probe or install. Clap uses `2` for usage errors.
+const EXIT_ERROR: i32 = 3;
+/// yt-dlp or ffmpeg is missing or does not run.
+const EXIT_MISSING_DEPENDENCY: i32 = 4;
+
+/// How often the terminal checks whether the queued jobs are done.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Downloads one or more URLs with progress bars, exits with 1 if any of them fails.
+    Download {
+        #[command(flatten)]
+        options: OptionsArgs,
+        /// Queues every entry when a URL is a playlist or channel.
+        #[arg(long)]
+        playlist: bool,
+        /// Defaults to the download directory of the user.
+        #[arg(long, short)]
+        output_dir: Option<PathBuf>,
+    },
+    /// Prints the metadata and formats of a URL as JSON.
+    Probe { url: String },
+    /// Installs yt-dlp and ffmpeg into the configured binary directory.
+    InstallDeps,
+    /// Prints whether yt-dlp, ffmpeg and ffprobe are found and run as JSON, exits with 4
+    /// if yt-dlp or ffmpeg does not.
+    CheckDeps,
+    /// Installs the latest yt-dlp of the configured channel, or the pinned release.
+    UpdateYtdlp {
+        /// Only prints the installed and the available release as JSON.
+        #[arg(long)]
+        check: bool,
+    },
+    /// Manages the download queue shared with the GUI.
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+    /// Prints past downloads as JSON lines, newest first.
+    History {
+        /// Only entries whose URL or output path contain this text.
+        #[arg(long, short)]
+        search: Option<String>,
+        #[arg(long, default_value_t = 0)]
+        page: u32,
+        #[arg(long, default_value_t = 50)]
+        page_size: u32,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum QueueCommand {
+    /// Prints the queued jobs as JSON lines.
+    List,
+    /// Adds URLs to the queue without downloading them, printing the id of each job.
+    Add {
+        #[command(flatten)]
+        options: OptionsArgs,
+    },
+    /// Removes a job that has not been started, by the id `list` prints.
+    Remove { job_id: JobId },
+    /// Downloads every queued job, exits with 1 if any of them fails.
+    Run {
+        /// Defaults to the download directory of the user.
+        #[arg(long, short)]
+        output_dir: Option<PathBuf>,
+    },
+}
+
+/// Command line form of [`DownloadOptions`].
+#[derive(Args, Debug, Clone)]
+pub struct OptionsArgs {
+    #[arg(required = true)]
+    urls: Vec<String>,
+    /// yt-dlp format selector, such as `137+140` or `bestvideo+bestaudio`.
+    #[arg(long, short, default_value_t = ytdlp::default_quality())]
+    quality: String,
+    #[arg(long, value_enum, default_value_t = Container::Mp4)]
+    container: Container,
+    /// yt-dlp output template, relative to the output directory.
+    #[arg(long, default_value_t = ytdlp::default_name_format())]
+    name_format: String,
+    /// Extracts audio only, with this codec.
+    #[arg(long, value_enum)]
+    audio: Option<AudioCodec>,
+    /// VBR quality from 0 (best) to 10 (worst) or a bitrate such as 192K.
+    #[arg(long, default_value = "5", requires = "audio")]
+    audio_quality: String,
+    #[arg(long, requires = "audio")]
+    embed_thumbnail: bool,
+    #[arg(long, requires = "audio")]
+    embed_metadata: bool,
+    /// Mitigation profile, such as `gentle` or `strict`, instead of the one configured for the site.
+    #[arg(long)]
+    mitigation: Option<String>,
+    /// Runs of each download before giving up on transient failures, instead of the configured retry policy.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_attempts: Option<u32>,
+}
+
+impl OptionsArgs {
+    fn download_options(&self) -> Vec<DownloadOptions> {
+        let audio = self.audio.map(|codec| {
+            AudioOptions::new(codec, self.audio_quality.clone(), self.embed_thumbnail, self.embed_metadata)
+        });
+        self.urls
+            .iter()
+            .map(|url| {
+                DownloadOptions::new(
+                    url.clone(),
+                    self.quality.clone(),
+                    self.container,
+                    self.name_format.clone(),
+                    audio.clone(),
+                )
+                .with_mitigation(self.mitigation.clone())
+                .with_retry(self.max_attempts.map(|max_attempts| RetryPolicy::default().with_max_attempts(max_attempts)))
+            })
+            .collect()
+    }
+}
+
+/// Runs `command` without creating a window and returns the process exit code.
+pub fn run(command: Command, log_level: LevelFilter) -> i32 {
+    if log::set_logger(&STDERR_LOGGER).is_ok() {
+        log::set_max_level(log_level);
+    }
+
+    match tauri::async_runtime::block_on(execute(command)) {
+        Ok(exit_code) => exit_code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            EXIT_ERROR
+        }
+    }
+}
+
+async fn execute(command: Command) -> Result<i32, VscraperError> {
+    let config_dir = app_dir(dirs::config_dir(), "config")?;
+
+    match command {
+        Command::Download {
+            options,
+            playlist,
+            output_dir,
+        } => {
+            let state = init_state(&config_dir, output_dir, DownloadQueue::default())?;
+            let (engine, renderer) = start_engine(state);
+            for options in options.download_options() {
+                let playlist_id = match playlist {
+                    true => engine.enqueue_playlist(options.clone(), None).await?,
+                    false => None,
+                };
+                // Single videos are queued as they are, even with `--playlist`.
+                if playlist_id.is_none() {
+                    engine.enqueue(options).await?;
+                }
+            }
+            wait_for_jobs(engine.state()).await;
+            Ok(renderer.stop(engine).await)
+        }
+        Command::Probe { url } => {
+            let config = Config::load(&config_dir)?;
+            let data_dir = app_dir(dirs::data_dir(), "data")?;
+            let auth = AuthStore::load(&config_dir, &data_dir)?.probe_auth(&url)?;
+            let info = probe::probe(&config, &url, &auth, &config.get_mitigation().profile_for_url(&url)).await?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            Ok(EXIT_SUCCESS)
+        }
+        Command::InstallDeps => {
+            let config = Config::load(&config_dir)?;
+            let ytdlp_updates = config.get_ytdlp_updates();
+            for (project, executable_name, tag) in [
+                (ytdlp_updates.repository(), components::YTDLP_EXECUTABLE, ytdlp_updates.pinned_tag()),
+                (components::FFMPEG_GITHUB, components::FFMPEG_EXECUTABLE, None),
+            ] {
+                let bar = InstallBar::new(executable_name);
+                let task = InstallTask::new(executable_name, bar.clone());
+                let result = components::install(project, executable_name, &config, tag, &task).await;
+                bar.0.finish_and_clear();
+                let installed = result?;
+                println!("installed {} {} to {}", executable_name, installed.tag(), config.get_binary_path().display());
+            }
+            Ok(EXIT_SUCCESS)
+        }
+        Command::CheckDeps => {
+            let config = Config::load(&config_dir)?;
+            let statuses = dependencies::check_all(&config).await;
+            println!("{}", serde_json::to_string_pretty(&statuses)?);
+            // yt-dlp only needs ffprobe for some postprocessing.
+            let required_missing =
+                statuses.iter().any(|status| status.dependency() != Dependency::Ffprobe && !status.runs());
+            Ok(if required_missing { EXIT_MISSING_DEPENDENCY } else { EXIT_SUCCESS })
+        }
+        Command::UpdateYtdlp { check } => {
+            let config = Config::load(&config_dir)?;
+            match check {
+                true => println!("{}", serde_json::to_string_pretty(&versions::check_ytdlp(&config).await?)?),
+                false => {
+                    let bar = InstallBar::new(components::YTDLP_EXECUTABLE);
+                    let task = InstallTask::new(components::YTDLP_EXECUTABLE, bar.clone());
+                    let result = versions::update_ytdlp(&config, &task).await;
+                    bar.0.finish_and_clear();
+                    println!("yt-dlp {}", result?.tag());
+                }
+            }
+            Ok(EXIT_SUCCESS)
+        }
+        Command::Queue { command } => {
+            let output_dir = match &command {
+                QueueCommand::Run { output_dir } => output_dir.clone(),
+                _ => None,
+            };
+            let state = init_state(&config_dir, output_dir, DownloadQueue::load(&config_dir))?;
+            let exit_code = match command {
+                QueueCommand::List => {
+                    for job in state.lock().await.queue().jobs() {
+                        println!("{}", serde_json::to_string(&job)?);
+                    }
+                    EXIT_SUCCESS
+                }
+                QueueCommand::Add { options } => {
+                    let config = state.lock().await.get_config();
+                    for options in options.download_options() {
+                        let auth = state.lock().await.auth().probe_auth(options.url())?;
+                        ytdlp::validate_options(&config, &options, &auth).await?;
+                        println!("{}", state.lock().await.queue_mut().enqueue(options, None));
+                    }
+                    EXIT_SUCCESS
+                }
+                QueueCommand::Remove { job_id } => match state.lock().await.queue_mut().remove(&job_id) {
+                    Some(_) => EXIT_SUCCESS,
+                    None => return Err(VscraperError::DownloadNotFound(job_id)),
+                },
+                QueueCommand::Run { .. } => {
+                    let (engine, renderer) = start_engine(state.clone());
+                    // Running the queue resumes the jobs a closed or crashed app left behind.
+                    let jobs = state.lock().await.queue().jobs();
+                    for job in jobs {
+                        if job.state() == JobState::Interrupted {
+                            log::info!("resuming interrupted download: {}", job.url());
+                            engine.resume(job.id()).await;
+                        }
+                    }
+                    engine.schedule();
+                    wait_for_jobs(engine.state()).await;
+                    renderer.stop(engine).await
+                }
+            };
+            state.lock().await.queue().save(&config_dir)?;
+            Ok(exit_code)
+        }
+        Command::History {
+            search,
+            page,
+            page_size,
+        } => {
+            let state = init_state(&config_dir, None, DownloadQueue::default())?;
+            let state = state.lock().await;
+            let entries = match search {
+                Some(query) => state.history().search(&query, page, page_size)?,
+                None => state.history().page(page, page_size)?,
+            };
+            for entry in entries {
+                println!("{}", serde_json::to_string(&entry)?);
+            }
+            Ok(EXIT_SUCCESS)
+        }
+    }
+}
+
+/// The app's directory in the platform directory `dir`, described as `kind` when the
+/// platform has none.
+fn app_dir(dir: Option<PathBuf>, kind: &str) -> Result<PathBuf, VscraperError> {
+    dir.map(|dir| dir.join(APP_IDENTIFIER)).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, format!("no {} directory on this platform", kind)).into()
+    })
+}
+
+fn init_state(
+    config_dir: &Path,
+    output_dir: Option<PathBuf>,
+    queue: DownloadQueue,
+) -> Result<Arc<Mutex<AppState>>, VscraperError> {
+    let data_dir = app_dir(dirs::data_dir(), "data")?;
+    let download_dir = match output_dir.or_else(dirs::download_dir) {
+        Some(download_dir) => download_dir,
+        None => std::env::current_dir()?,
+    };
+    Ok(Arc::new(Mutex::new(AppState::init(config_dir, &data_dir, download_dir, queue)?)))
+}
+
+/// Engine for a headless run. Its events are drawn on a task of their own, so a slow
+/// terminal never holds up reading yt-dlp's output, and are logged instead when stderr
+/// is not a terminal.
+fn start_engine(state: Arc<Mutex<AppState>>) -> (DownloadEngine, Renderer) {
+    let (sink, mut events) = ChannelSink::new();
+    let output: Arc<dyn EventSink> = match std::io::stderr().is_terminal() {
+        true => Arc::new(TerminalSink::default()),
+        false => Arc::new(LoggingSink),
+    };
+    let task = tauri::async_runtime::spawn(async move {
+        // Ended jobs leave the queue, so their outcome is taken from the events.
+        let mut all_finished = true;
+        while let Some((emission, payload)) = events.recv().await {
+            if emission == Emission::YtdlpDownloadFinish {
+                all_finished &= payload["state"] == JobState::Finished.as_str();
+            }
+            output.emit(emission, payload);
+        }
+        all_finished
+    });
+    (DownloadEngine::new(state, Arc::new(sink)), Renderer { task })
+}
+
+struct Renderer {
+    /// Resolves to whether every job that ended was finished.
+    task: tauri::async_runtime::JoinHandle<bool>,
+}
+
+impl Renderer {
+    /// Drops `engine`, waits until the events it already sent are drawn and returns
+    /// the exit code for the jobs that ended.
+    async fn stop(self, engine: DownloadEngine) -> i32 {
+        drop(engine);
+        match self.task.await {
+            Ok(true) => EXIT_SUCCESS,
+            Ok(false) => EXIT_DOWNLOAD_FAILED,
+            Err(err) => {
+                log::error!("rendering events, err: {}", err);
+                EXIT_ERROR
+            }
+        }
+    }
+}
+
+/// Waits until no job is queued, running or paused.
+async fn wait_for_jobs(state: &Mutex<AppState>) {
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+        if state.lock().await.queue().jobs().iter().all(|job| !job.state().is_active()) {
+            return;
+        }
+    }
+}
+
+/// Draws one progress bar per download on stderr, when it is a terminal.
+#[derive(Default)]
+struct TerminalSink {
+    bars: MultiProgress,
+    /// Bars by job id, jobs for the same URL get a bar each.
+    downloads: std::sync::Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl TerminalSink {
+    fn bar(&self, job_id: &str, url: &str) -> ProgressBar {
+        let mut downloads = self.downloads.lock().unwrap_or_else(|err| err.into_inner());
+        downloads
+            .entry(job_id.to_string())
+            .or_insert_with(|| {
+                let style = ProgressStyle::with_template("{prefix:.bold} {wide_bar} {bytes}/{total_bytes} {msg}")
+                    .unwrap_or_else(|_| ProgressStyle::default_bar());
+                self.bars.add(ProgressBar::new(0).with_style(style).with_prefix(url.to_string()))
+            })
+            .clone()
+    }
+
+    fn update(&self, progress: &serde_json::Value) {
+        let (Some(job_id), Some(url)) = (progress["job_id"].as_str(), progress["url"].as_str()) else {
+            return;
+        };
+        let bar = self.bar(job_id, url);
+        if let Some(total_bytes) = progress["total_bytes"].as_u64() {
+            bar.set_length(total_bytes);
+        }
+        if let Some(downloaded_bytes) = progress["downloaded_bytes"].as_u64() {
+            bar.set_position(downloaded_bytes);
+        }
+
+        let mut message = progress["phase"].as_str().unwrap_or_default().replace('_', " ");
+        if let Some(speed) = progress["speed"].as_f64() {
+            message.push_str(&format!(" {}/s", HumanBytes(speed as u64)));
+        }
+        if let Some(eta) = progress["eta"].as_u64() {
+            message.push_str(&format!(" eta {}s", eta));
+        }
+        bar.set_message(message);
+    }
+
+    fn finish(&self, finished: &serde_json::Value) {
+        let (Some(job_id), Some(state)) = (finished["job_id"].as_str(), finished["state"].as_str()) else {
+            return;
+        };
+        let downloads = self.downloads.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(bar) = downloads.get(job_id).filter(|bar| !bar.is_finished()) {
+            bar.finish_with_message(state.to_string());
+        }
+    }
+}
+
+impl EventSink for TerminalSink {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        match emission {
+            Emission::YtdlpDownloadUpdate => self.update(&payload),
+            Emission::YtdlpDownloadFinish => self.finish(&payload),
+            Emission::YtdlpDownloadError => {
+                let url = payload["url"].as_str().unwrap_or_default();
+                let reason = payload["stderr_tail"]
+                    .as_array()
+                    .and_then(|lines| lines.last())
+                    .and_then(|line| line.as_str())
+                    .unwrap_or_default();
+                let message = format!(
+                    "{} failed ({}): {}",
+                    url,
+                    payload["error"].as_str().unwrap_or("unknown"),
+                    reason
+                );
+                let _ = self.bars.println(message);
+            }
+            _ => log::trace!("event: {}, payload: {}", emission.as_string(), payload),
+        }
+    }
+}
+
+/// Draws the progress of one install, which reports nothing else as events.
+struct InstallBar(ProgressBar);
+
+impl InstallBar {
+    fn new(executable_name: &str) -> Arc<InstallBar> {
+        let style = ProgressStyle::with_template("{prefix:.bold} {wide_bar} {bytes}/{total_bytes} {msg}")
+            .unwrap_or_else(|_| ProgressStyle::default_bar());
+        Arc::new(InstallBar(ProgressBar::new(0).with_style(style).with_prefix(executable_name.to_string())))
+    }
+}
+
+impl EventSink for InstallBar {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        if emission != Emission::InstallProgress {
+            return;
+        }
+        if let Some(total_bytes) = payload["total_bytes"].as_u64() {
+            self.0.set_length(total_bytes);
+        }
+        if let Some(downloaded_bytes) = payload["downloaded_bytes"].as_u64() {
+            self.0.set_position(downloaded_bytes);
+        }
+        self.0.set_message(payload["phase"].as_str().unwrap_or_default().replace('_', " "));
+    }
+}
+
+/// Logger for headless runs, the log plugin is only set up with the Tauri app.
+struct StderrLogger;
+
+static STDERR_LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -1,83 +1,222 @@
+use std::fs;
+use std::path::Path;
 use std::sync::{Arc};
 use tauri::State;
-use tauri_plugin_log::log::error;
+use tauri_plugin_log::log::{info, warn};
 use tokio::sync::Mutex;
-use ubi::UbiBuilder;
 
-use crate::{app_state::AppState, emissions::Emission, emit_and_handle_result};
+use crate::{
+    app_state::AppState,
+    checksum::ChecksumVerification,
+    config::Config,
+    emissions::{Emission, InstallPhase},
+    error::VscraperError,
+    install_source,
+    installs::{InstallManager, InstallTask},
+    versions::InstalledVersions,
+};
 
 pub const FFMPEG_EXECUTABLE: &str = "ffmpeg";
-const FFMPEG_GITHUB: &str = "eugeneware/ffmpeg-static";
+pub const FFMPEG_GITHUB: &str = "eugeneware/ffmpeg-static";
 pub const YTDLP_EXECUTABLE: &str = "yt-dlp";
-const YTDLP_GITHUB: &str = "yt-dlp/yt-dlp";
+pub const YTDLP_GITHUB: &str = "yt-dlp/yt-dlp";
+
+/// A release [`install`] put in place, or found already installed.
+#[derive(Debug)]
+pub struct Installed {
+    tag: String,
+    /// `None` when nothing was downloaded, or a local file had no checksum to check.
+    checksum: Option<ChecksumVerification>,
+}
+
+impl Installed {
+    pub fn up_to_date(tag: String) -> Installed {
+        Installed { tag, checksum: None }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn checksum(&self) -> Option<&ChecksumVerification> {
+        self.checksum.as_ref()
+    }
+}
 
 #[tauri::command]
 pub async fn install_ytdlp(
-    app_handle: tauri::AppHandle,
+    installs: State<'_, InstallManager>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> tauri::Result<()> {
-    let install_path = state.lock().await.get_config().get_binary_path();
+) -> Result<(), VscraperError> {
+    let config = state.lock().await.get_config();
+    // Follows the configured channel and pin, like updates do.
+    let ytdlp_updates = config.get_ytdlp_updates().clone();
     install_lib(
-        app_handle,
-        String::from(YTDLP_GITHUB),
-        String::from(YTDLP_EXECUTABLE),
-        install_path,
+        &installs,
+        ytdlp_updates.repository(),
+        YTDLP_EXECUTABLE,
+        ytdlp_updates.pinned_tag().map(String::from),
+        config,
         Emission::YtdlpInstall,
-    );
-
-    Ok(())
+    )
 }
 
 #[tauri::command]
 pub async fn install_ffmpeg(
-    app_handle: tauri::AppHandle,
+    installs: State<'_, InstallManager>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> tauri::Result<()> {
-    let install_path = state.lock().await.get_config().get_binary_path();
-    install_lib(
-        app_handle,
-        String::from(FFMPEG_GITHUB),
-        String::from(FFMPEG_EXECUTABLE),
-        install_path,
-        Emission::FfmpegInstall,
-    );
+) -> Result<(), VscraperError> {
+    let config = state.lock().await.get_config();
+    install_lib(&installs, FFMPEG_GITHUB, FFMPEG_EXECUTABLE, None, config, Emission::FfmpegInstall)
+}
 
+/// Name of the release asset holding `executable_name` built for this platform.
+pub fn release_asset(executable_name: &str) -> Result<String, VscraperError> {
+    use std::env::consts::{ARCH, OS};
+    let asset = match (executable_name, OS, ARCH) {
+        (YTDLP_EXECUTABLE, "linux", "x86_64") => Some(String::from("yt-dlp_linux")),
+        (YTDLP_EXECUTABLE, "linux", "aarch64") => Some(String::from("yt-dlp_linux_aarch64")),
+        (YTDLP_EXECUTABLE, "macos", _) => Some(String::from("yt-dlp_macos")),
+        (YTDLP_EXECUTABLE, "windows", "x86_64") => Some(String::from("yt-dlp.exe")),
+        (YTDLP_EXECUTABLE, "windows", "x86") => Some(String::from("yt-dlp_x86.exe")),
+        (FFMPEG_EXECUTABLE, ..) => {
+            let platform = match OS {
+                "macos" => Some("darwin"),
+                "windows" => Some("win32"),
+                "linux" => Some("linux"),
+                _ => None,
+            };
+            let arch = match ARCH {
+                "x86_64" => Some("x64"),
+                "x86" => Some("ia32"),
+                "aarch64" => Some("arm64"),
+                "arm" => Some("arm"),
+                _ => None,
+            };
+            platform.zip(arch).map(|(platform, arch)| format!("{}-{}-{}", FFMPEG_EXECUTABLE, platform, arch))
+        }
+        _ => None,
+    };
+    asset.ok_or_else(|| VscraperError::Install(format!("no {} build for {} {}", executable_name, OS, ARCH)))
+}
+
+/// Writes `data` next to `path` and renames it over `path`, so the previous binary stays
+/// in place until the new one is complete.
+fn activate(path: &Path, data: &[u8]) -> Result<(), VscraperError> {
+    let staged = path.with_extension("download");
+    fs::write(&staged, data)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+    }
+    fs::rename(&staged, path).inspect_err(|_| {
+        let _ = fs::remove_file(&staged);
+    })?;
     Ok(())
 }
 
-fn install_lib(
-    app_handle: tauri::AppHandle,
-    git_format: String,
-    executable_name: String,
-    install_path: std::path::PathBuf,
-    emission: Emission,
-) {
-    std::thread::spawn(move || {
-        let ubi = UbiBuilder::new()
-            .project(&git_format)
-            .install_dir(install_path)
-            .rename_exe_to(&executable_name)
-            .build();
-
-        match ubi {
-            Ok(mut ubi) => {
-                tauri::async_runtime::block_on(async {
-                    let install_result = ubi.install_binary().await;
-                    emit_and_handle_result(&app_handle, emission, install_result.is_ok());
-                });
+/// Installs release `tag` of `project`, the latest one when `None`, from the configured
+/// [`InstallSource`](install_source::InstallSource) into the binary directory, reporting
+/// each phase to `task`. The binary only replaces the installed one when it matches the
+/// checksum published for it.
+pub async fn install(
+    project: &str,
+    executable_name: &str,
+    config: &Config,
+    tag: Option<&str>,
+    task: &InstallTask,
+) -> Result<Installed, VscraperError> {
+    let source = config.get_install_sources().get(executable_name);
+    let fetched = source.fetch(config.get_network(), project, executable_name, tag, task).await?;
+
+    task.phase(InstallPhase::Verifying)?;
+    let checksum = match fetched.expected_sha256 {
+        Some(expected) => {
+            let checksum = ChecksumVerification::new(&fetched.asset, expected, &fetched.data);
+            if !checksum.verified() {
+                return Err(VscraperError::Checksum(checksum));
             }
-            Err(err) => error!("building ubi installer for {}: {}", git_format, err),
+            Some(checksum)
         }
-    });
+        None => None,
+    };
+    if install_source::is_archive(&fetched.asset) {
+        task.phase(InstallPhase::Extracting)?;
+    }
+    let binary = install_source::extract(&fetched.asset, fetched.data, executable_name)?;
+
+    // Past this point the install is not cancelled, the binary is replaced in one rename.
+    task.phase(InstallPhase::Activating)?;
+    let install_path = config.get_binary_path();
+    fs::create_dir_all(&install_path)?;
+    activate(
+        &install_path.join(format!("{}{}", executable_name, std::env::consts::EXE_SUFFIX)),
+        &binary,
+    )?;
+    let repository = source.repository(project);
+    InstalledVersions::record(&install_path, executable_name, &repository, &fetched.tag)?;
+    match checksum {
+        Some(_) => info!("installed {} {} with verified sha256", repository, fetched.tag),
+        None => warn!("installed {} {} without a checksum to verify", repository, fetched.tag),
+    }
+
+    Ok(Installed {
+        tag: fetched.tag,
+        checksum,
+    })
+}
+
+fn install_lib(
+    installs: &InstallManager,
+    project: &'static str,
+    executable_name: &'static str,
+    tag: Option<String>,
+    config: Config,
+    emission: Emission,
+) -> Result<(), VscraperError> {
+    installs.start(executable_name, emission, move |task| async move {
+        install(project, executable_name, &config, tag.as_deref(), &task).await
+    })
 }
 
 #[tauri::command]
 pub async fn install_ffmpeg_ytdlp(
-    app_handle: tauri::AppHandle,
+    installs: State<'_, InstallManager>,
     state: State<'_, Arc<Mutex<AppState>>>,
-) -> tauri::Result<()> {
-    install_ffmpeg(app_handle.clone(), state.clone()).await?;
-    install_ytdlp(app_handle, state).await?;
+) -> Result<(), VscraperError> {
+    install_ffmpeg(installs.clone(), state.clone()).await?;
+    install_ytdlp(installs, state).await?;
 
     Ok(())
 }
+
+#[test]
+fn test_local_install_is_verified() {
+    let dir = crate::testing::TempDir::new("components");
+    let local = dir.join("downloads").join("yt-dlp_linux");
+    fs::create_dir_all(local.parent().unwrap()).unwrap();
+    fs::write(&local, "yt-dlp binary").unwrap();
+    let config = |sha256: String| -> Config {
+        serde_json::from_value(serde_json::json!({
+            "binary_install_path": dir.join("libs"),
+            "install_sources": { "ytdlp": { "type": "local", "path": local, "sha256": sha256 } },
+        }))
+        .unwrap()
+    };
+
+    let task = InstallTask::new(YTDLP_EXECUTABLE, Arc::new(crate::events::LoggingSink));
+    tauri::async_runtime::block_on(async {
+        let mismatch = install(YTDLP_GITHUB, YTDLP_EXECUTABLE, &config("0".repeat(64)), None, &task).await;
+        assert!(matches!(mismatch, Err(VscraperError::Checksum(_))));
+        assert!(!dir.join("libs").join(YTDLP_EXECUTABLE).exists());
+
+        let checksum = crate::checksum::sha256_hex(b"yt-dlp binary");
+        let installed =
+            install(YTDLP_GITHUB, YTDLP_EXECUTABLE, &config(checksum), Some("2025.01.15"), &task).await.unwrap();
+        assert_eq!(installed.tag(), "2025.01.15");
+        assert!(installed.checksum().unwrap().verified());
+    });
+    assert_eq!(fs::read_to_string(dir.join("libs").join(YTDLP_EXECUTABLE)).unwrap(), "yt-dlp binary");
+    assert!(InstalledVersions::load(&dir.join("libs")).get(YTDLP_EXECUTABLE).is_some());
+}
@@ -1,9 +1,21 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri_plugin_log::log::error;
 
 use crate::components;
+use crate::dependencies::{self, Dependency};
+use crate::error::VscraperError;
+use crate::install_source::InstallSources;
+use crate::mitigation::MitigationSettings;
+use crate::network::NetworkSettings;
+use crate::retry::RetryPolicy;
+use crate::versions::YtdlpUpdateSettings;
 
 pub const CONFIG_FILENAME: &str = "settings.json";
+const CONFIG_BACKUP_FILENAME: &str = "settings.json.bak";
+/// History database in the app data directory, unless the config names another one.
+const HISTORY_FILENAME: &str = "history.sqlite";
 
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 pub struct Config {
@@ -13,11 +25,37 @@ pub struct Config {
     #[serde(default = "default_ffmpeg_path")]
     pub ffmpeg_path: PathBuf,
 
+    /// `None` keeps the history in the app data directory.
+    #[serde(default)]
+    history_path: Option<PathBuf>,
+
+    #[serde(default)]
+    install_sources: InstallSources,
+
+    #[serde(default = "default_max_parallel_downloads")]
+    max_parallel_downloads: usize,
+
+    #[serde(default)]
+    mitigation: MitigationSettings,
+
+    #[serde(default)]
+    network: NetworkSettings,
+
+    #[serde(default)]
+    retry: RetryPolicy,
+
     #[serde(default)]
     skip_homepage: bool,
 
+    /// Runs yt-dlp, ffmpeg and ffprobe found on `PATH` instead of the configured ones.
+    #[serde(default)]
+    use_system_binaries: bool,
+
     #[serde(default = "default_ytdlp_path")]
     ytdlp_path: PathBuf,
+
+    #[serde(default)]
+    ytdlp_updates: YtdlpUpdateSettings,
 }
 
 fn default_binary_path() -> PathBuf {
@@ -32,37 +70,158 @@ fn default_ytdlp_path() -> PathBuf {
     default_binary_path().join(components::YTDLP_EXECUTABLE)
 }
 
+fn default_max_parallel_downloads() -> usize {
+    3
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             binary_install_path: default_binary_path(),
             ffmpeg_path: default_ffmpeg_path(),
+            history_path: None,
+            install_sources: InstallSources::default(),
+            max_parallel_downloads: default_max_parallel_downloads(),
+            mitigation: MitigationSettings::default(),
+            network: NetworkSettings::default(),
+            retry: RetryPolicy::default(),
             skip_homepage: false,
+            use_system_binaries: false,
             ytdlp_path: default_ytdlp_path(),
+            ytdlp_updates: YtdlpUpdateSettings::default(),
         }
     }
 }
 
 impl Config {
+    /// Reads the config in `dir`, using defaults when there is none yet. A config that
+    /// fails to parse is moved to `settings.json.bak` and replaced with the defaults.
+    pub fn load(dir: &Path) -> Result<Config, VscraperError> {
+        fs::create_dir_all(dir)?;
+        let file = dir.join(CONFIG_FILENAME);
+        let file_data = match fs::read(&file) {
+            Ok(file_data) => file_data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        match serde_json::from_slice(&file_data) {
+            Ok(config) => Ok(config),
+            Err(err) => {
+                let backup = dir.join(CONFIG_BACKUP_FILENAME);
+                error!("corrupt {}: {}, backing up to {}", CONFIG_FILENAME, err, backup.display());
+                fs::rename(&file, &backup)?;
+                let config = Config::default();
+                fs::write(&file, serde_json::to_string_pretty(&config)?)?;
+                Ok(config)
+            }
+        }
+    }
+
     pub fn get_binary_path(&self) -> PathBuf {
         self.binary_install_path.clone()
     }
 
+    /// The yt-dlp downloads run, see [`dependencies::resolve`].
     pub fn get_ytdlp_path(&self) -> PathBuf {
-        self.ytdlp_path.clone()
+        dependencies::resolve(self, Dependency::Ytdlp).0
     }
 
     pub fn get_ffmpeg_path(&self) -> PathBuf {
+        dependencies::resolve(self, Dependency::Ffmpeg).0
+    }
+
+    pub fn get_configured_ytdlp_path(&self) -> PathBuf {
+        self.ytdlp_path.clone()
+    }
+
+    pub fn get_configured_ffmpeg_path(&self) -> PathBuf {
         self.ffmpeg_path.clone()
     }
 
+    pub fn get_use_system_binaries(&self) -> bool {
+        self.use_system_binaries
+    }
+
+    pub fn set_use_system_binaries(&mut self, use_system_binaries: bool) {
+        self.use_system_binaries = use_system_binaries;
+    }
+
+    /// The configured history database, or the default one in `data_dir`.
+    pub fn get_history_path(&self, data_dir: &Path) -> PathBuf {
+        match &self.history_path {
+            Some(history_path) => history_path.clone(),
+            None => data_dir.join(HISTORY_FILENAME),
+        }
+    }
+
+    pub fn get_max_parallel_downloads(&self) -> usize {
+        self.max_parallel_downloads
+    }
+
+    pub fn get_mitigation(&self) -> &MitigationSettings {
+        &self.mitigation
+    }
+
+    pub fn set_mitigation(&mut self, mitigation: MitigationSettings) {
+        self.mitigation = mitigation;
+    }
+
+    pub fn get_network(&self) -> &NetworkSettings {
+        &self.network
+    }
+
+    pub fn set_network(&mut self, network: NetworkSettings) {
+        self.network = network;
+    }
+
+    pub fn get_retry(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub fn set_retry(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub fn get_install_sources(&self) -> &InstallSources {
+        &self.install_sources
+    }
+
+    pub fn set_install_sources(&mut self, install_sources: InstallSources) {
+        self.install_sources = install_sources;
+    }
+
+    pub fn get_ytdlp_updates(&self) -> &YtdlpUpdateSettings {
+        &self.ytdlp_updates
+    }
+
+    pub fn set_ytdlp_updates(&mut self, ytdlp_updates: YtdlpUpdateSettings) {
+        self.ytdlp_updates = ytdlp_updates;
+    }
+
     pub fn set_skip_homepage(&mut self, new_preference: bool) {
         self.skip_homepage = new_preference;
     }
+
+    pub fn set_max_parallel_downloads(&mut self, max_parallel_downloads: usize) {
+        // A limit of zero would stall the queue forever.
+        self.max_parallel_downloads = max_parallel_downloads.max(1);
+    }
 }
 
 #[test]
 fn test_default_config() {
     let serde_conf: Config = serde_json::from_str("{}").unwrap();
     assert_eq!(serde_conf, Config::default());
+    assert_eq!(serde_conf.get_history_path(Path::new("/data")), Path::new("/data/history.sqlite"));
+}
+
+#[test]
+fn test_corrupt_config_is_backed_up() {
+    let dir = crate::testing::TempDir::new("config");
+    fs::write(dir.join(CONFIG_FILENAME), "{ not json").unwrap();
+
+    assert_eq!(Config::load(&dir).unwrap(), Config::default());
+    assert_eq!(fs::read_to_string(dir.join(CONFIG_BACKUP_FILENAME)).unwrap(), "{ not json");
+    assert_eq!(Config::load(&dir).unwrap(), Config::default());
 }
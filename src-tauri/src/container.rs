@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::probe::MediaFormat;
+
+/// Output containers yt-dlp merges or remuxes downloads into with ffmpeg.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    #[default]
+    Mp4,
+    Mkv,
+    Webm,
+    Mov,
+}
+
+impl Container {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mkv => "mkv",
+            Container::Webm => "webm",
+            Container::Mov => "mov",
+        }
+    }
+
+    /// Arguments making yt-dlp write this container, preferring formats that fit it
+    /// when the quality is a generic selector such as `best`.
+    pub fn ytdlp_args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("--merge-output-format"),
+            String::from(self.as_str()),
+            String::from("--remux-video"),
+            String::from(self.as_str()),
+        ];
+        let preferred_ext = match self {
+            Container::Mp4 | Container::Mov => Some("ext:mp4:m4a"),
+            Container::Webm => Some("ext:webm:webm"),
+            Container::Mkv => None,
+        };
+        if let Some(preferred_ext) = preferred_ext {
+            args.push(String::from("-S"));
+            args.push(String::from(preferred_ext));
+        }
+        args
+    }
+
+    fn supports_video(&self, vcodec: &str) -> bool {
+        let codec_family = ["avc", "h264", "hvc", "hev", "h265", "av01", "vp8", "vp9", "vp09", "mp4v", "prores"]
+            .into_iter()
+            .find(|family| vcodec.starts_with(family));
+        match (self, codec_family) {
+            (Container::Mkv, _) => true,
+            (Container::Mp4, Some(family)) => family != "vp8" && family != "prores",
+            (Container::Mov, Some(family)) => {
+                ["avc", "h264", "hvc", "hev", "h265", "mp4v", "prores"].contains(&family)
+            }
+            (Container::Webm, Some(family)) => ["av01", "vp8", "vp9", "vp09"].contains(&family),
+            (_, None) => false,
+        }
+    }
+
+    fn supports_audio(&self, acodec: &str) -> bool {
+        let codec_family = ["mp4a", "aac", "mp3", "opus", "vorbis", "flac", "alac", "ac-3", "ec-3", "pcm"]
+            .into_iter()
+            .find(|family| acodec.starts_with(family));
+        match (self, codec_family) {
+            (Container::Mkv, _) => true,
+            (Container::Mp4, Some(family)) => family != "vorbis" && family != "pcm",
+            (Container::Mov, Some(family)) => ["mp4a", "aac", "mp3", "alac", "ac-3", "pcm"].contains(&family),
+            (Container::Webm, Some(family)) => family == "opus" || family == "vorbis",
+            (_, None) => false,
+        }
+    }
+
+    /// Checks that the formats picked by an explicit `quality` such as `137+140` can be
+    /// muxed into this container. Selectors that are not plain format ids are left to yt-dlp.
+    pub fn check_formats(&self, quality: &str, formats: &[MediaFormat]) -> Result<(), ContainerError> {
+        let picked: Option<Vec<&MediaFormat>> = quality
+            .split('+')
+            .map(|format_id| formats.iter().find(|format| format.format_id == format_id))
+            .collect();
+
+        for format in picked.unwrap_or_default() {
+            let unsupported = format
+                .vcodec
+                .as_deref()
+                .filter(|vcodec| !self.supports_video(vcodec))
+                .or(format.acodec.as_deref().filter(|acodec| !self.supports_audio(acodec)));
+            if let Some(codec) = unsupported {
+                return Err(ContainerError {
+                    container: *self,
+                    format_id: format.format_id.clone(),
+                    codec: String::from(codec),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A picked format whose codec the container cannot hold.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ContainerError {
+    container: Container,
+    format_id: String,
+    codec: String,
+}
+
+impl Display for ContainerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "format {} uses codec {} which cannot be muxed into {}, pick another format or container",
+            self.format_id,
+            self.codec,
+            self.container.as_str()
+        )
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+#[test]
+fn test_container_check_formats() {
+    let formats: Vec<MediaFormat> = serde_json::from_str(
+        r#"[
+            { "format_id": "137", "ext": "mp4", "vcodec": "avc1.640028", "acodec": "none" },
+            { "format_id": "248", "ext": "webm", "vcodec": "vp9", "acodec": "none" },
+            { "format_id": "140", "ext": "m4a", "vcodec": "none", "acodec": "mp4a.40.2" },
+            { "format_id": "251", "ext": "webm", "vcodec": "none", "acodec": "opus" }
+        ]"#,
+    )
+    .unwrap();
+
+    assert!(Container::Mp4.check_formats("137+140", &formats).is_ok());
+    assert!(Container::Webm.check_formats("248+251", &formats).is_ok());
+    assert!(Container::Mkv.check_formats("137+251", &formats).is_ok());
+    assert!(Container::Webm.check_formats("bestvideo+bestaudio", &formats).is_ok());
+
+    let err = Container::Webm.check_formats("137+251", &formats).unwrap_err();
+    assert_eq!(err.format_id, "137");
+    assert!(Container::Mov.check_formats("248+140", &formats).is_err());
+}
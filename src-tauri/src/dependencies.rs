@@ -0,0 +1,233 @@
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use ts_rs::TS;
+
+use crate::app_state::AppState;
+use crate::components::{FFMPEG_EXECUTABLE, YTDLP_EXECUTABLE};
+use crate::config::Config;
+use crate::error::VscraperError;
+
+pub const FFPROBE_EXECUTABLE: &str = "ffprobe";
+
+/// A binary that hangs on `--version` is reported as not running instead of blocking the check.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum Dependency {
+    Ytdlp,
+    Ffmpeg,
+    Ffprobe,
+}
+
+impl Dependency {
+    pub const ALL: [Dependency; 3] = [Dependency::Ytdlp, Dependency::Ffmpeg, Dependency::Ffprobe];
+
+    pub fn executable_name(&self) -> &'static str {
+        match self {
+            Dependency::Ytdlp => YTDLP_EXECUTABLE,
+            Dependency::Ffmpeg => FFMPEG_EXECUTABLE,
+            Dependency::Ffprobe => FFPROBE_EXECUTABLE,
+        }
+    }
+
+    fn version_arg(&self) -> &'static str {
+        match self {
+            Dependency::Ytdlp => "--version",
+            Dependency::Ffmpeg | Dependency::Ffprobe => "-version",
+        }
+    }
+}
+
+/// Where the path a dependency runs from came from.
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum DependencySource {
+    /// The binary directory the installers write to.
+    Managed,
+    /// Found on `PATH`, used when the config opts into system binaries.
+    Path,
+    /// A path set in the config outside the binary directory.
+    Configured,
+}
+
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct DependencyStatus {
+    dependency: Dependency,
+    #[ts(type = "string")]
+    path: PathBuf,
+    source: DependencySource,
+    found: bool,
+    executable: bool,
+    /// First line of the `--version` output, `None` when the binary did not run.
+    version: Option<String>,
+}
+
+impl DependencyStatus {
+    pub fn dependency(&self) -> Dependency {
+        self.dependency
+    }
+
+    /// Whether `--version` succeeded, the binary may still be missing codecs.
+    pub fn runs(&self) -> bool {
+        self.version.is_some()
+    }
+}
+
+/// The first executable named `executable_name` in the `PATH`-style list `paths`.
+pub fn find_in(executable_name: &str, paths: &OsStr) -> Option<PathBuf> {
+    std::env::split_paths(paths)
+        .map(|dir| dir.join(format!("{}{}", executable_name, std::env::consts::EXE_SUFFIX)))
+        .find(|path| is_executable(path))
+}
+
+pub fn find_on_path(executable_name: &str) -> Option<PathBuf> {
+    find_in(executable_name, &std::env::var_os("PATH")?)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The path `dependency` runs from and where it came from, a binary on `PATH` wins over
+/// the configured one when the config opts into system binaries.
+pub fn resolve(config: &Config, dependency: Dependency) -> (PathBuf, DependencySource) {
+    if config.get_use_system_binaries() {
+        if let Some(path) = find_on_path(dependency.executable_name()) {
+            return (path, DependencySource::Path);
+        }
+    }
+    let path = match dependency {
+        Dependency::Ytdlp => config.get_configured_ytdlp_path(),
+        Dependency::Ffmpeg => config.get_configured_ffmpeg_path(),
+        // yt-dlp looks for ffprobe next to the ffmpeg it is given.
+        Dependency::Ffprobe => config.get_configured_ffmpeg_path().with_file_name(FFPROBE_EXECUTABLE),
+    };
+    let source = match path.starts_with(config.get_binary_path()) {
+        true => DependencySource::Managed,
+        false => DependencySource::Configured,
+    };
+    (path, source)
+}
+
+/// Whether the binary `dependency` resolves to exists and runs, and its version.
+pub async fn check(config: &Config, dependency: Dependency) -> DependencyStatus {
+    let (mut path, source) = resolve(config, dependency);
+    // The installers add the platform's suffix, the configured paths leave it out.
+    let with_suffix = path.with_file_name(format!(
+        "{}{}",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        std::env::consts::EXE_SUFFIX
+    ));
+    if !path.exists() && with_suffix.exists() {
+        path = with_suffix;
+    }
+
+    let found = path.is_file();
+    let executable = is_executable(&path);
+    let version = match executable {
+        true => version(&path, dependency.version_arg()).await,
+        false => None,
+    };
+    DependencyStatus {
+        dependency,
+        path,
+        source,
+        found,
+        executable,
+        version,
+    }
+}
+
+async fn version(path: &Path, version_arg: &str) -> Option<String> {
+    let output = Command::new(path)
+        .arg(version_arg)
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .output();
+    let output = tokio::time::timeout(VERSION_TIMEOUT, output).await.ok()?.ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).lines().next().map(|line| line.trim().to_string())
+}
+
+pub async fn check_all(config: &Config) -> Vec<DependencyStatus> {
+    let mut statuses = Vec::new();
+    for dependency in Dependency::ALL {
+        statuses.push(check(config, dependency).await);
+    }
+    statuses
+}
+
+#[tauri::command]
+pub async fn check_dependencies(
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<Vec<DependencyStatus>, VscraperError> {
+    let config = state.lock().await.get_config();
+    Ok(check_all(&config).await)
+}
+
+#[tauri::command]
+pub async fn update_use_system_binaries(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    use_system_binaries: bool,
+) -> Result<(), VscraperError> {
+    state.lock().await.set_use_system_binaries(use_system_binaries);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_dependency_check() {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = crate::testing::TempDir::new("dependencies");
+    let ytdlp_path = dir.join(YTDLP_EXECUTABLE);
+    fs::write(&ytdlp_path, "#!/bin/sh\necho 2025.01.15\n").unwrap();
+    fs::set_permissions(&ytdlp_path, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(dir.join(FFMPEG_EXECUTABLE), "").unwrap();
+
+    let paths = std::env::join_paths([Path::new("/nonexistent"), &*dir]).unwrap();
+    assert_eq!(find_in(YTDLP_EXECUTABLE, &paths), Some(ytdlp_path.clone()));
+    assert_eq!(find_in(FFMPEG_EXECUTABLE, &paths), None);
+
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "binary_install_path": &*dir,
+        "ytdlp_path": ytdlp_path,
+        "ffmpeg_path": dir.join(FFMPEG_EXECUTABLE),
+    }))
+    .unwrap();
+    let statuses = tauri::async_runtime::block_on(check_all(&config));
+    let summary: Vec<_> = statuses
+        .iter()
+        .map(|status| (status.dependency, status.source, status.found, status.executable, status.version.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (Dependency::Ytdlp, DependencySource::Managed, true, true, Some("2025.01.15")),
+            (Dependency::Ffmpeg, DependencySource::Managed, true, false, None),
+            (Dependency::Ffprobe, DependencySource::Managed, false, false, None),
+        ]
+    );
+    assert_eq!(statuses[2].path, dir.join(FFPROBE_EXECUTABLE));
+}
@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use ts_rs::TS;
+
+use crate::queue::{Job, JobId};
+
+/// Number of trailing stderr lines kept for diagnostics.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Why yt-dlp failed, classified from its stderr.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum DownloadError {
+    UnsupportedUrl,
+    GeoBlocked,
+    /// Private, removed or otherwise unavailable video.
+    Unavailable,
+    LoginRequired,
+    AgeRestricted,
+    /// Bot detection or captcha challenge.
+    BotChallenge,
+    /// HTTP 429 Too Many Requests.
+    RateLimited,
+    FfmpegMissing,
+    DiskFull,
+    Network,
+    Unknown,
+}
+
+/// Patterns checked in order, so more specific messages such as "Sign in to confirm
+/// your age" or "Private video. Sign in if.." win over generic ones such as "Sign in".
+const ERROR_PATTERNS: [(DownloadError, &[&str]); 10] = [
+    (
+        DownloadError::BotChallenge,
+        &["not a bot", "captcha", "unusual traffic", "challenge"],
+    ),
+    (
+        DownloadError::AgeRestricted,
+        &["confirm your age", "age-restricted", "age restricted", "inappropriate for some users"],
+    ),
+    (
+        DownloadError::RateLimited,
+        &["http error 429", "too many requests", "rate-limit", "rate limit"],
+    ),
+    (
+        DownloadError::GeoBlocked,
+        &["not available in your country", "geo restriction", "geo-restricted", "geo restricted"],
+    ),
+    (
+        DownloadError::Unavailable,
+        &[
+            "private video",
+            "video unavailable",
+            "has been removed",
+            "no longer available",
+            "does not exist",
+            "http error 404",
+            "http error 410",
+        ],
+    ),
+    (
+        DownloadError::LoginRequired,
+        &[
+            "login required",
+            "sign in",
+            "log in",
+            "registered users",
+            "requires authentication",
+            "use --cookies",
+            "--username",
+        ],
+    ),
+    (
+        DownloadError::UnsupportedUrl,
+        &["unsupported url", "is not a valid url", "no suitable infoextractor"],
+    ),
+    (
+        DownloadError::FfmpegMissing,
+        &["ffmpeg not found", "ffprobe and ffmpeg not found", "ffmpeg is not installed", "ffprobe not found"],
+    ),
+    (
+        DownloadError::DiskFull,
+        &["no space left on device", "errno 28", "disk full", "disk quota exceeded"],
+    ),
+    (
+        DownloadError::Network,
+        &[
+            "unable to download webpage",
+            "unable to download video data",
+            "connection refused",
+            "connection reset",
+            "timed out",
+            "temporary failure in name resolution",
+            "name or service not known",
+            "getaddrinfo failed",
+            "network is unreachable",
+            "urlopen error",
+            "ssl:",
+        ],
+    ),
+];
+
+impl DownloadError {
+    /// Classifies a failure from the stderr of yt-dlp, `ERROR:` lines take precedence
+    /// over warnings that may mention unrelated problems.
+    pub fn classify(stderr: &[String]) -> DownloadError {
+        let error_lines: Vec<String> = stderr
+            .iter()
+            .filter(|line| line.starts_with("ERROR:"))
+            .map(|line| line.to_lowercase())
+            .collect();
+        let lines = match error_lines.is_empty() {
+            true => stderr.iter().map(|line| line.to_lowercase()).collect(),
+            false => error_lines,
+        };
+
+        ERROR_PATTERNS
+            .iter()
+            .find(|(_, patterns)| {
+                lines
+                    .iter()
+                    .any(|line| patterns.iter().any(|pattern| line.contains(pattern)))
+            })
+            .map_or(DownloadError::Unknown, |(error, _)| *error)
+    }
+}
+
+/// Payload sent to the frontend when a job fails.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct DownloadFailure {
+    job_id: JobId,
+    url: String,
+    error: DownloadError,
+    exit_code: Option<i32>,
+    stderr_tail: Vec<String>,
+}
+
+impl DownloadFailure {
+    pub fn new(job: &Job, exit_code: Option<i32>, stderr_tail: Vec<String>) -> DownloadFailure {
+        DownloadFailure {
+            job_id: job.id().clone(),
+            url: String::from(job.url()),
+            error: DownloadError::classify(&stderr_tail),
+            exit_code,
+            stderr_tail,
+        }
+    }
+
+    pub fn error(&self) -> DownloadError {
+        self.error
+    }
+}
+
+/// The last lines a child wrote to stderr.
+#[derive(Debug, Default)]
+pub struct StderrTail(VecDeque<String>);
+
+impl StderrTail {
+    pub fn push(&mut self, line: String) {
+        if self.0.len() == STDERR_TAIL_LINES {
+            self.0.pop_front();
+        }
+        self.0.push_back(line);
+    }
+
+    pub fn into_lines(self) -> Vec<String> {
+        self.0.into()
+    }
+}
+
+/// Reads stderr until it closes, keeping the last lines.
+pub async fn collect_stderr_tail<T: AsyncRead + Unpin>(stderr: T) -> Vec<String> {
+    let mut lines = BufReader::new(stderr).lines();
+    let mut tail = StderrTail::default();
+    while let Ok(Some(line)) = lines.next_line().await {
+        tail.push(line);
+    }
+    tail.into_lines()
+}
+
+#[test]
+fn test_classify_download_errors() {
+    let classify = |lines: &[&str]| {
+        DownloadError::classify(&lines.iter().map(|line| line.to_string()).collect::<Vec<String>>())
+    };
+
+    assert_eq!(
+        classify(&["ERROR: [generic] Unsupported URL: https://example.com"]),
+        DownloadError::UnsupportedUrl
+    );
+    assert_eq!(
+        classify(&["ERROR: [youtube] abc: Sign in to confirm you're not a bot. Use --cookies"]),
+        DownloadError::BotChallenge
+    );
+    assert_eq!(
+        classify(&["ERROR: [youtube] abc: Sign in to confirm your age."]),
+        DownloadError::AgeRestricted
+    );
+    assert_eq!(
+        classify(&[
+            "WARNING: [youtube] Unable to download webpage: timed out",
+            "ERROR: unable to download video data: HTTP Error 429: Too Many Requests",
+        ]),
+        DownloadError::RateLimited
+    );
+    assert_eq!(
+        classify(&["ERROR: [youtube] abc: Private video. Sign in if you've been granted access"]),
+        DownloadError::Unavailable
+    );
+    assert_eq!(
+        classify(&["ERROR: [vimeo] 123: This video is only available for registered users"]),
+        DownloadError::LoginRequired
+    );
+    assert_eq!(
+        classify(&["ERROR: [youtube] abc: Video unavailable. This video has been removed by the uploader"]),
+        DownloadError::Unavailable
+    );
+    assert_eq!(
+        classify(&["ERROR: Postprocessing: ffprobe and ffmpeg not found."]),
+        DownloadError::FfmpegMissing
+    );
+    assert_eq!(
+        classify(&["ERROR: unable to write data: [Errno 28] No space left on device"]),
+        DownloadError::DiskFull
+    );
+    assert_eq!(classify(&["something odd happened"]), DownloadError::Unknown);
+}
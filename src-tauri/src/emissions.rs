@@ -1,9 +1,31 @@
-#[allow(dead_code)]
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ts_rs::TS;
+
+use crate::checksum::ChecksumVerification;
+use crate::components::Installed;
+use crate::download_error::DownloadError;
+use crate::error::VscraperError;
+use crate::queue::{Job, JobId, JobState};
+
+/// Version of the payloads below, bump it whenever one of them changes shape. The
+/// frontend drops events whose version it was not built for.
+pub const EVENT_SCHEMA_VERSION: u32 = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
 pub enum Emission {
     FfmpegInstall,
+    InstallProgress,
     YtdlpCancelDownload,
+    YtdlpDownloadError,
     YtdlpDownloadUpdate,
     YtdlpInstall,
+    YtdlpPauseDownload,
+    YtdlpPlaylistUpdate,
+    YtdlpResumeDownload,
     YtdlpUrlUpdate,
     YtdlpDownloadFinish,
 }
@@ -12,11 +34,234 @@ impl Emission {
     pub fn as_string(&self) -> &'static str {
         match self {
             Emission::FfmpegInstall => "ffmpeg_install",
+            Emission::InstallProgress => "install_progress",
             Emission::YtdlpCancelDownload => "ytdlp_cancel_download",
+            Emission::YtdlpDownloadError => "ytdlp_download_error",
             Emission::YtdlpDownloadUpdate => "ytdlp_download_update",
             Emission::YtdlpDownloadFinish => "ytdlp_download_finish",
             Emission::YtdlpInstall => "ytdlp_install",
+            Emission::YtdlpPauseDownload => "ytdlp_pause_download",
+            Emission::YtdlpPlaylistUpdate => "ytdlp_playlist_update",
+            Emission::YtdlpResumeDownload => "ytdlp_resume_download",
             Emission::YtdlpUrlUpdate => "ytdlp_url_update",
         }
     }
 }
+
+/// Every payload the webview receives is wrapped in this.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct EventEnvelope<T> {
+    version: u32,
+    payload: T,
+}
+
+impl<T> EventEnvelope<T> {
+    pub fn new(payload: T) -> EventEnvelope<T> {
+        EventEnvelope {
+            version: EVENT_SCHEMA_VERSION,
+            payload,
+        }
+    }
+}
+
+/// Payload of [`Emission::YtdlpUrlUpdate`], sent once yt-dlp has checked the job's URL.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct UrlChecked {
+    job_id: JobId,
+    url: String,
+    available: bool,
+}
+
+impl UrlChecked {
+    pub fn new(job: &Job, available: bool) -> UrlChecked {
+        UrlChecked {
+            job_id: job.id().clone(),
+            url: job.url().to_string(),
+            available,
+        }
+    }
+}
+
+/// Payload of [`Emission::YtdlpPauseDownload`], [`Emission::YtdlpResumeDownload`] and
+/// [`Emission::YtdlpCancelDownload`].
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct JobStateChanged {
+    job_id: JobId,
+    url: String,
+    state: JobState,
+}
+
+impl JobStateChanged {
+    pub fn new(job: &Job, state: JobState) -> JobStateChanged {
+        JobStateChanged {
+            job_id: job.id().clone(),
+            url: job.url().to_string(),
+            state,
+        }
+    }
+}
+
+/// Payload of [`Emission::YtdlpDownloadFinish`], sent once for every job that was started.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct DownloadFinished {
+    job_id: JobId,
+    url: String,
+    /// Finished, failed or cancelled.
+    state: JobState,
+    exit_code: Option<i32>,
+    output_path: Option<PathBuf>,
+    /// Milliseconds since the job was started, pauses included.
+    #[ts(type = "number")]
+    duration_ms: u64,
+    /// Set when the job failed, the details come with [`Emission::YtdlpDownloadError`].
+    error: Option<DownloadError>,
+}
+
+impl DownloadFinished {
+    pub fn new(
+        job: &Job,
+        state: JobState,
+        exit_code: Option<i32>,
+        output_path: Option<PathBuf>,
+        duration: Duration,
+        error: Option<DownloadError>,
+    ) -> DownloadFinished {
+        DownloadFinished {
+            job_id: job.id().clone(),
+            url: job.url().to_string(),
+            state,
+            exit_code,
+            output_path,
+            duration_ms: duration.as_millis() as u64,
+            error,
+        }
+    }
+
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    pub fn exit_code(&self) -> Option<i32> {
+        self.exit_code
+    }
+
+    pub fn output_path(&self) -> Option<&Path> {
+        self.output_path.as_deref()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum InstallPhase {
+    ResolvingRelease,
+    Downloading,
+    Verifying,
+    Extracting,
+    Activating,
+}
+
+/// Payload of [`Emission::InstallProgress`], sent on every phase change and a few times a
+/// second while downloading.
+#[derive(Clone, Debug, Serialize, PartialEq, TS)]
+#[ts(export)]
+pub struct InstallProgress {
+    executable: String,
+    phase: InstallPhase,
+    #[ts(type = "number")]
+    downloaded_bytes: u64,
+    /// `None` when the server does not send a length.
+    #[ts(type = "number | null")]
+    total_bytes: Option<u64>,
+}
+
+impl InstallProgress {
+    pub fn new(executable: &str) -> InstallProgress {
+        InstallProgress {
+            executable: executable.to_string(),
+            phase: InstallPhase::ResolvingRelease,
+            downloaded_bytes: 0,
+            total_bytes: None,
+        }
+    }
+
+    pub fn executable(&self) -> &str {
+        &self.executable
+    }
+
+    pub fn set_phase(&mut self, phase: InstallPhase) {
+        self.phase = phase;
+    }
+
+    pub fn set_bytes(&mut self, downloaded_bytes: u64, total_bytes: Option<u64>) {
+        self.downloaded_bytes = downloaded_bytes;
+        self.total_bytes = total_bytes;
+    }
+}
+
+/// Why an install failed, `kind` is one of the kinds commands fail with.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct InstallFailure {
+    kind: String,
+    message: String,
+}
+
+/// Payload of [`Emission::YtdlpInstall`] and [`Emission::FfmpegInstall`].
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct InstallFinished {
+    executable: String,
+    /// Release tag installed now, `None` when the install failed.
+    version: Option<String>,
+    /// The downloaded binary checked against its release, `None` when nothing was
+    /// downloaded or the install failed before the check.
+    checksum: Option<ChecksumVerification>,
+    /// `None` on success, kind `install_cancelled` when the install was cancelled.
+    error: Option<InstallFailure>,
+}
+
+impl InstallFinished {
+    pub fn new(executable: &str, result: &Result<Installed, VscraperError>) -> InstallFinished {
+        match result {
+            Ok(installed) => InstallFinished {
+                executable: executable.to_string(),
+                version: Some(installed.tag().to_string()),
+                checksum: installed.checksum().cloned(),
+                error: None,
+            },
+            Err(err) => InstallFinished {
+                executable: executable.to_string(),
+                version: None,
+                checksum: match err {
+                    VscraperError::Checksum(checksum) => Some(checksum.clone()),
+                    _ => None,
+                },
+                error: Some(InstallFailure {
+                    kind: err.kind().to_string(),
+                    message: err.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+/// Writes [`EVENT_SCHEMA_VERSION`] next to the bindings `#[ts(export)]` generates, so the
+/// frontend checks events against the version it was built with.
+#[test]
+fn export_bindings_event_schema_version() {
+    let dir = PathBuf::from(std::env::var("TS_RS_EXPORT_DIR").unwrap_or_else(|_| String::from("./bindings")));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("EventSchemaVersion.ts"),
+        format!(
+            "// This file was generated by `cargo test`. Do not edit this file manually.\n\nexport const EVENT_SCHEMA_VERSION = {};\n",
+            EVENT_SCHEMA_VERSION
+        ),
+    )
+    .unwrap();
+}
@@ -0,0 +1,591 @@
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri_plugin_log::log::{debug, error, info, trace, warn};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Child;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::app_state::AppState;
+use crate::download_error::{collect_stderr_tail, DownloadError, DownloadFailure, StderrTail};
+use crate::emissions::{DownloadFinished, Emission, JobStateChanged, UrlChecked};
+use crate::error::VscraperError;
+use crate::events::EventSink;
+use crate::playlist::{self, PlaylistSelection};
+use crate::progress::DownloadProgress;
+use crate::queue::{ControlMessage, Job, JobId, JobState, PlaylistProgress};
+use crate::ytdlp::{self, DownloadOptions};
+
+/// How long a yt-dlp child gets to exit after SIGTERM before it is killed.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// How often [`DownloadEngine::shutdown`] checks whether every job has stopped.
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Queues, runs and controls downloads, reporting what happens to an [`EventSink`].
+/// Cloning is cheap, clones share the same state and sink.
+#[derive(Clone)]
+pub struct DownloadEngine {
+    state: Arc<Mutex<AppState>>,
+    events: Arc<dyn EventSink>,
+    /// Set by [`DownloadEngine::shutdown`], no job is started afterwards.
+    stopping: Arc<AtomicBool>,
+}
+
+impl DownloadEngine {
+    pub fn new(state: Arc<Mutex<AppState>>, events: Arc<dyn EventSink>) -> DownloadEngine {
+        DownloadEngine {
+            state,
+            events,
+            stopping: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn state(&self) -> &Mutex<AppState> {
+        &self.state
+    }
+
+    /// Validates `options` and queues a job for them.
+    pub async fn enqueue(&self, options: DownloadOptions) -> Result<JobId, VscraperError> {
+        let (config, auth) = {
+            let state = self.state.lock().await;
+            (state.get_config(), state.auth().probe_auth(options.url())?)
+        };
+        ytdlp::validate_options(&config, &options, &auth).await?;
+
+        let id = self.state.lock().await.queue_mut().enqueue(options, None);
+        self.schedule();
+        Ok(id)
+    }
+
+    /// Queues one job per selected entry of the playlist at `options.url`, sharing the
+    /// remaining options. Returns the playlist id the jobs are grouped under, or `None`
+    /// when `options.url` is a single video. The options are validated against the first
+    /// selected entry, the others share its site.
+    pub async fn enqueue_playlist(
+        &self,
+        options: DownloadOptions,
+        selection: Option<PlaylistSelection>,
+    ) -> Result<Option<String>, VscraperError> {
+        // Checked before listing the playlist, every entry would fail the same way.
+        options.validate_audio()?;
+        let (config, auth) = {
+            let state = self.state.lock().await;
+            (state.get_config(), state.auth().probe_auth(options.url())?)
+        };
+        let mitigation = config.get_mitigation().profile_for(&options);
+        let playlist = match playlist::enumerate(&config, options.url(), &auth, &mitigation).await? {
+            Some(playlist) => playlist,
+            None => {
+                error!("not a playlist: {}", options.url());
+                return Ok(None);
+            }
+        };
+
+        let selection = selection.unwrap_or_default();
+        let entries: Vec<DownloadOptions> = playlist
+            .entries()
+            .iter()
+            .filter(|entry| selection.contains(entry.index()))
+            .map(|entry| options.with_url(entry.url()))
+            .collect();
+        if let Some(first) = entries.first() {
+            ytdlp::validate_options(&config, first, &auth).await?;
+        }
+        {
+            let mut state = self.state.lock().await;
+            for entry_options in entries {
+                state.queue_mut().enqueue(entry_options, Some(playlist.id().to_string()));
+            }
+        }
+        self.schedule();
+
+        Ok(Some(playlist.id().to_string()))
+    }
+
+    /// Starts as many queued jobs as `Config::max_parallel_downloads` allows.
+    pub fn schedule(&self) {
+        if self.stopping.load(Ordering::SeqCst) {
+            return;
+        }
+        let engine = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let (ready, playlists) = {
+                let mut state = engine.state.lock().await;
+                let max_parallel = state.get_config().get_max_parallel_downloads();
+                let ready = state.queue_mut().start_next(max_parallel);
+
+                let mut playlist_ids: Vec<&str> = ready.iter().filter_map(|(job, _)| job.playlist_id()).collect();
+                playlist_ids.sort_unstable();
+                playlist_ids.dedup();
+                let playlists: Vec<PlaylistProgress> = playlist_ids
+                    .into_iter()
+                    .map(|playlist_id| state.queue().playlist_progress(playlist_id))
+                    .collect();
+                (ready, playlists)
+            };
+
+            for progress in playlists {
+                engine.events.send(Emission::YtdlpPlaylistUpdate, progress);
+            }
+            for (job, rx) in ready {
+                debug!("starting queued download for url: {}", job.url());
+                tauri::async_runtime::spawn(engine.clone().run(job, rx));
+            }
+        });
+    }
+
+    /// Stops a running or paused job, or drops it from the queue if it has not started yet.
+    pub async fn cancel(&self, id: JobId) -> Result<(), VscraperError> {
+        let (job, has_control) = {
+            let state = self.state.lock().await;
+            (state.queue().get(&id).cloned(), state.queue().get_control(&id).is_some())
+        };
+        let cancelled = match has_control {
+            true => self.send_control(&id, ControlMessage::Cancel).await,
+            false => self.state.lock().await.queue_mut().cancel_queued(&id),
+        };
+        match (cancelled, job) {
+            (true, Some(job)) => {
+                self.events.send(Emission::YtdlpCancelDownload, JobStateChanged::new(&job, JobState::Cancelled));
+                Ok(())
+            }
+            _ => Err(VscraperError::DownloadNotFound(id)),
+        }
+    }
+
+    pub async fn pause(&self, id: &JobId) -> bool {
+        self.send_control(id, ControlMessage::Pause).await
+    }
+
+    /// Resumes a paused job, or queues a job interrupted by a shutdown again.
+    pub async fn resume(&self, id: &JobId) -> bool {
+        if self.state.lock().await.queue_mut().requeue_interrupted(id) {
+            self.schedule();
+            return true;
+        }
+        self.send_control(id, ControlMessage::Resume).await
+    }
+
+    /// Stops every started job so it continues from its partial file on next launch,
+    /// giving them up to `timeout` to exit. Nothing is started afterwards.
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.stopping.store(true, Ordering::SeqCst);
+        let controls = self.state.lock().await.queue().controls();
+        for tx in controls {
+            // The job may end by itself meanwhile.
+            let _ = tx.send(ControlMessage::Shutdown).await;
+        }
+
+        let stopped = tokio::time::timeout(timeout, async {
+            while !self.state.lock().await.queue().controls().is_empty() {
+                tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+            }
+        })
+        .await;
+        if stopped.is_err() {
+            error!("downloads still running after {:?}, they are interrupted on next launch", timeout);
+        }
+    }
+
+    /// Sends a control message to a running or paused job.
+    async fn send_control(&self, id: &JobId, message: ControlMessage) -> bool {
+        let tx = self.state.lock().await.queue().get_control(id).cloned();
+        match tx {
+            Some(tx) => tx.send(message).await.is_ok(),
+            None => {
+                error!("no running download with id: {}", id);
+                false
+            }
+        }
+    }
+
+    /// Runs a job started by the scheduler to completion, then hands its slot back. `rx`
+    /// receives the pause, resume and cancel messages for the job.
+    async fn run(self, job: Job, mut rx: mpsc::Receiver<ControlMessage>) {
+        let started = Instant::now();
+        let options = job.options().clone();
+        let url = options.url();
+        let (config, download_dir, auth) = {
+            let state = self.state.lock().await;
+            let auth = state.auth().job_auth(job.id(), url);
+            (state.get_config(), state.download_dir().to_path_buf(), auth)
+        };
+        let mitigations = config.get_mitigation();
+        let mut mitigation = mitigations.profile_for(&options);
+        // Each profile is tried at most once, whatever its `escalate_to` says.
+        let mut tried_mitigations = vec![mitigation.name().to_string()];
+        let retry = options.retry().unwrap_or(config.get_retry()).clone();
+        // Escalating to a stricter mitigation profile does not count as an attempt.
+        let mut attempt = 1;
+        let history_id = {
+            let state = self.state.lock().await;
+            match state.history().record_start(&options) {
+                Ok(id) => Some(id),
+                Err(err) => {
+                    error!("recording history for url: {}, err: {}", url, err);
+                    None
+                }
+            }
+        };
+
+        let auth = match auth {
+            Ok(auth) => auth,
+            Err(err) => {
+                let error = self.report_failure(DownloadFailure::new(&job, None, vec![err.to_string()]));
+                let finished = DownloadFinished::new(&job, JobState::Failed, None, None, started.elapsed(), Some(error));
+                self.finish(&job, history_id, finished).await;
+                return;
+            }
+        };
+
+        let playlist_sleep = mitigation.playlist_sleep();
+        if job.playlist_id().is_some() && !playlist_sleep.is_zero() {
+            debug!("waiting {:?} before playlist entry: {}", playlist_sleep, url);
+            if let Some(job_state) = self.wait_unless_stopped(&job, &mut rx, playlist_sleep).await {
+                let finished = DownloadFinished::new(&job, job_state, None, None, started.elapsed(), None);
+                self.finish(&job, history_id, finished).await;
+                return;
+            }
+        }
+
+        loop {
+            debug!("checking url availability for: {}, mitigation: {}", url, mitigation.name());
+            let checked = ytdlp::check_url_availability(&config, &options, &auth, &mitigation).await;
+            let output = match checked {
+                Ok(output) => output,
+                Err(err) => {
+                    // The download would fail to spawn the same yt-dlp.
+                    error!("executing command: {}", err);
+                    let error = self.report_failure(DownloadFailure::new(&job, None, vec![err.to_string()]));
+                    let finished =
+                        DownloadFinished::new(&job, JobState::Failed, None, None, started.elapsed(), Some(error));
+                    self.finish(&job, history_id, finished).await;
+                    return;
+                }
+            };
+            if !output.status.success() {
+                let stderr_tail = collect_stderr_tail(output.stderr.as_slice()).await;
+                let failure = DownloadFailure::new(&job, output.status.code(), stderr_tail);
+                if let Some(stricter) = mitigations.escalate(&mitigation, failure.error(), &tried_mitigations) {
+                    warn!("url check for: {} hit {:?}, retrying with mitigation: {}", url, failure.error(), stricter.name());
+                    tried_mitigations.push(stricter.name().to_string());
+                    mitigation = stricter;
+                    continue;
+                }
+                if let Some(delay) = retry.delay(attempt, failure.error()) {
+                    warn!("url check for: {} hit {:?}, retrying in {:?}", url, failure.error(), delay);
+                    attempt += 1;
+                    let Some(job_state) = self.wait_unless_stopped(&job, &mut rx, delay).await else {
+                        continue;
+                    };
+                    let finished = DownloadFinished::new(&job, job_state, None, None, started.elapsed(), None);
+                    self.finish(&job, history_id, finished).await;
+                    return;
+                }
+                self.events.send(Emission::YtdlpUrlUpdate, UrlChecked::new(&job, false));
+                let error = self.report_failure(failure);
+                let finished = DownloadFinished::new(
+                    &job,
+                    JobState::Failed,
+                    output.status.code(),
+                    None,
+                    started.elapsed(),
+                    Some(error),
+                );
+                self.finish(&job, history_id, finished).await;
+                return;
+            }
+            self.events.send(Emission::YtdlpUrlUpdate, UrlChecked::new(&job, true));
+            break;
+        }
+
+        let mut exit_code = None;
+        let mut error = None;
+        let mut output_path = None;
+        let mut resume = job.is_resume();
+        let job_state = loop {
+            debug!("downloading from url: {}, mitigation: {}, attempt: {}", url, mitigation.name(), attempt);
+            let spawned = ytdlp::download_command(&config, &download_dir, &job, &auth, &mitigation, resume)
+                .stderr(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn();
+            let mut child = match spawned {
+                Ok(child) => child,
+                Err(err) => {
+                    error = Some(self.report_failure(DownloadFailure::new(&job, None, vec![err.to_string()])));
+                    break JobState::Failed;
+                }
+            };
+
+            debug!("spawned ytdlp download from url: {}, with pid: {}", url, child.id().map_or("unknown".to_string(), |code| code.to_string()));
+
+            // Both pipes were requested above, so they are always present.
+            let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+                kill_child(&mut child, url).await;
+                break JobState::Failed;
+            };
+            let mut stdout = BufReader::new(stdout).lines();
+            let mut stderr = BufReader::new(stderr).lines();
+            let (mut stdout_open, mut stderr_open) = (true, true);
+            let mut stderr_tail = StderrTail::default();
+
+            let control = loop {
+                if !stdout_open && !stderr_open {
+                    break None;
+                }
+                tokio::select! {
+                    line = stdout.next_line(), if stdout_open => match line {
+                        Ok(Some(line)) => {
+                            trace!("ytdlp: {}", line);
+                            if let Some(path) = ytdlp::parse_output_path(&line) {
+                                output_path = Some(path);
+                            }
+                            if let Some(progress) = DownloadProgress::parse(job.id(), url, &line) {
+                                self.events.send(Emission::YtdlpDownloadUpdate, progress.with_attempt(attempt));
+                            }
+                        }
+                        Ok(None) | Err(_) => stdout_open = false,
+                    },
+                    // `--print` makes yt-dlp quiet, which moves its progress to stderr.
+                    line = stderr.next_line(), if stderr_open => match line {
+                        Ok(Some(line)) => {
+                            trace!("ytdlp stderr: {}", line);
+                            match DownloadProgress::parse(job.id(), url, &line) {
+                                Some(progress) => {
+                                    self.events.send(Emission::YtdlpDownloadUpdate, progress.with_attempt(attempt));
+                                }
+                                None => stderr_tail.push(line),
+                            }
+                        }
+                        Ok(None) | Err(_) => stderr_open = false,
+                    },
+                    message = rx.recv() => match message {
+                        // Resuming a running download is a no-op.
+                        Some(ControlMessage::Resume) => {},
+                        Some(message) => break Some(message),
+                        None => break Some(ControlMessage::Cancel),
+                    },
+                }
+            };
+
+            match control {
+                None => match child.wait().await {
+                    Ok(status) => {
+                        exit_code = status.code();
+                        let job_state = match status.success() {
+                            true => JobState::Finished,
+                            false => JobState::Failed,
+                        };
+                        if !status.success() {
+                            let failure = DownloadFailure::new(&job, exit_code, stderr_tail.into_lines());
+                            if let Some(stricter) = mitigations.escalate(&mitigation, failure.error(), &tried_mitigations) {
+                                warn!("download of: {} hit {:?}, retrying with mitigation: {}", url, failure.error(), stricter.name());
+                                tried_mitigations.push(stricter.name().to_string());
+                                mitigation = stricter;
+                                resume = true;
+                                continue;
+                            }
+                            if let Some(delay) = retry.delay(attempt, failure.error()) {
+                                warn!("download of: {} hit {:?}, retrying in {:?}", url, failure.error(), delay);
+                                attempt += 1;
+                                resume = true;
+                                match self.wait_unless_stopped(&job, &mut rx, delay).await {
+                                    None => continue,
+                                    Some(job_state) => break job_state,
+                                }
+                            }
+                            error = Some(self.report_failure(failure));
+                        }
+                        break job_state;
+                    },
+                    Err(err) => {
+                        error!("download with url: {}, failed with err: {}", url, err);
+                        break JobState::Failed;
+                    }
+                },
+                Some(ControlMessage::Pause) => {
+                    debug!("received pause signal for url: {}", url);
+                    kill_child(&mut child, url).await;
+                    match self.pause_until_resumed(&job, &mut rx).await {
+                        None => resume = true,
+                        Some(job_state) => break job_state,
+                    }
+                },
+                Some(ControlMessage::Shutdown) => {
+                    debug!("received shutdown signal for url: {}", url);
+                    terminate_child(&mut child, url).await;
+                    break JobState::Interrupted;
+                },
+                Some(_) => {
+                    debug!("received kill signal for url: {}", url);
+                    kill_child(&mut child, url).await;
+                    break JobState::Cancelled;
+                },
+            }
+        };
+
+        let finished = DownloadFinished::new(&job, job_state, exit_code, output_path, started.elapsed(), error);
+        self.finish(&job, history_id, finished).await;
+    }
+
+    /// Records the outcome of `job`, reports it and hands its slot to the next queued job.
+    async fn finish(&self, job: &Job, history_id: Option<i64>, finished: DownloadFinished) {
+        let playlist = {
+            let mut state = self.state.lock().await;
+            if let Some(id) = history_id {
+                let recorded =
+                    state.history().record_finish(id, finished.state(), finished.exit_code(), finished.output_path());
+                if let Err(err) = recorded {
+                    error!("recording history for url: {}, err: {}", job.url(), err);
+                }
+            }
+            state.queue_mut().finish(job.id(), finished.state())
+        };
+        self.events.send(Emission::YtdlpDownloadFinish, finished);
+        if let Some(progress) = playlist {
+            self.events.send(Emission::YtdlpPlaylistUpdate, progress);
+        }
+        self.schedule();
+    }
+
+    /// Waits `delay` before a job starts or is retried. Returns the state the job ends in
+    /// when it is cancelled or shut down meanwhile. A pause holds the job until it is
+    /// resumed, which skips the rest of the wait.
+    async fn wait_unless_stopped(
+        &self,
+        job: &Job,
+        rx: &mut mpsc::Receiver<ControlMessage>,
+        delay: Duration,
+    ) -> Option<JobState> {
+        let sleep = tokio::time::sleep(delay);
+        tokio::pin!(sleep);
+        loop {
+            tokio::select! {
+                _ = &mut sleep => return None,
+                message = rx.recv() => match message {
+                    Some(ControlMessage::Pause) => {
+                        debug!("received pause signal while waiting, url: {}", job.url());
+                        return self.pause_until_resumed(job, rx).await;
+                    }
+                    Some(ControlMessage::Resume) => {},
+                    Some(ControlMessage::Cancel) | None => return Some(JobState::Cancelled),
+                    Some(ControlMessage::Shutdown) => return Some(JobState::Interrupted),
+                },
+            }
+        }
+    }
+
+    /// Marks `job` paused and blocks until it is resumed (`None`), or returns the state it
+    /// ends in when it is cancelled or shut down instead.
+    async fn pause_until_resumed(&self, job: &Job, rx: &mut mpsc::Receiver<ControlMessage>) -> Option<JobState> {
+        self.state.lock().await.queue_mut().set_state(job.id(), JobState::Paused);
+        self.events.send(Emission::YtdlpPauseDownload, JobStateChanged::new(job, JobState::Paused));
+        self.emit_playlist_progress(job).await;
+
+        let ended = wait_for_resume(rx).await;
+        if ended.is_none() {
+            debug!("received resume signal for url: {}", job.url());
+            self.state.lock().await.queue_mut().set_state(job.id(), JobState::Running);
+            self.events.send(Emission::YtdlpResumeDownload, JobStateChanged::new(job, JobState::Running));
+            self.emit_playlist_progress(job).await;
+        }
+        ended
+    }
+
+    /// Reports why a job failed and returns the classified error.
+    fn report_failure(&self, failure: DownloadFailure) -> DownloadError {
+        error!("download failed: {:?}", failure);
+        let error = failure.error();
+        self.events.send(Emission::YtdlpDownloadError, failure);
+        error
+    }
+
+    /// Emits the aggregate state of the playlist `job` belongs to, if any.
+    async fn emit_playlist_progress(&self, job: &Job) {
+        if let Some(playlist_id) = job.playlist_id() {
+            let progress = self.state.lock().await.queue().playlist_progress(playlist_id);
+            self.events.send(Emission::YtdlpPlaylistUpdate, progress);
+        }
+    }
+}
+
+/// Blocks a paused job until it is resumed (`None`), or returns the state it ends in
+/// when it is cancelled or shut down instead.
+async fn wait_for_resume(rx: &mut mpsc::Receiver<ControlMessage>) -> Option<JobState> {
+    loop {
+        match rx.recv().await {
+            Some(ControlMessage::Resume) => return None,
+            Some(ControlMessage::Pause) => {},
+            Some(ControlMessage::Cancel) | None => return Some(JobState::Cancelled),
+            Some(ControlMessage::Shutdown) => return Some(JobState::Interrupted),
+        }
+    }
+}
+
+/// Asks the child to exit with SIGTERM so yt-dlp can close its partial file, killing it
+/// when it has not exited after [`SHUTDOWN_GRACE_PERIOD`].
+async fn terminate_child(child: &mut Child, url: &str) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        // SAFETY: `pid` is our own child, which has not been waited on yet.
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        if let Ok(Ok(status)) = tokio::time::timeout(SHUTDOWN_GRACE_PERIOD, child.wait()).await {
+            info!("child for url: {}, pid: {} exited on shutdown with: {}", url, pid, status);
+            return;
+        }
+    }
+    kill_child(child, url).await;
+}
+
+async fn kill_child(child: &mut Child, url: &str) {
+    let pid = child.id().map_or("unknown".to_string(), |code| code.to_string());
+    match child.kill().await {
+        Ok(_) => {
+            // `kill` also waits on the child, so no zombie is left behind.
+            info!("successfully killed child for url: {}, pid: {}", url, pid);
+        },
+        Err(err) => error!("failed to kill child for url: {}, pid: {} err: {}", url, pid, err),
+    }
+}
+
+#[test]
+fn test_engine_reports_invalid_url() {
+    use crate::events::RecorderSink;
+    use crate::queue::DownloadQueue;
+
+    let dir = crate::testing::TempDir::new("engine");
+    std::fs::write(
+        dir.join("settings.json"),
+        r#"{ "ytdlp_path": "/nonexistent/yt-dlp", "history_path": ":memory:" }"#,
+    )
+    .unwrap();
+    let state = AppState::init(&dir, &dir, dir.to_path_buf(), DownloadQueue::default()).unwrap();
+    let recorder = Arc::new(RecorderSink::default());
+    let engine = DownloadEngine::new(Arc::new(Mutex::new(state)), recorder.clone());
+
+    let url = "htt://www.youtube.com/watch?v=dQw4w9WgXcQ";
+    let options = DownloadOptions::for_url(url);
+    let finished = tauri::async_runtime::block_on(async {
+        engine.enqueue(options).await.unwrap();
+        let finished = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                if let Some(finished) = recorder.payloads(Emission::YtdlpDownloadFinish).pop() {
+                    break finished;
+                }
+            }
+        })
+        .await;
+        finished.unwrap_or_else(|_| panic!("job did not end, last event: {:?}", recorder.events().pop()))
+    });
+
+    assert_eq!(finished["state"], "failed");
+    let failures = recorder.payloads(Emission::YtdlpDownloadError);
+    // The availability check already fails to spawn yt-dlp, no download is attempted.
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["url"], url);
+    assert_eq!(failures[0]["exit_code"], serde_json::Value::Null);
+    assert!(recorder.payloads(Emission::YtdlpUrlUpdate).is_empty());
+}
@@ -0,0 +1,75 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+use crate::checksum::ChecksumVerification;
+use crate::container::ContainerError;
+use crate::queue::JobId;
+
+/// Errors returned to the frontend by every command.
+#[derive(Debug, thiserror::Error)]
+pub enum VscraperError {
+    #[error("io: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("tauri: {0}")]
+    Tauri(#[from] tauri::Error),
+    #[error("json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("history database: {0}")]
+    History(#[from] rusqlite::Error),
+    #[error("{0}")]
+    Container(#[from] ContainerError),
+    #[error("invalid audio options: {0}")]
+    InvalidAudioOptions(String),
+    /// yt-dlp exited unsuccessfully, holds the last line of its stderr.
+    #[error("yt-dlp failed for {url}: {reason}")]
+    Ytdlp { url: String, reason: String },
+    #[error("no download with id: {0}")]
+    DownloadNotFound(JobId),
+    #[error("{0}")]
+    Install(String),
+    #[error("auth: {0}")]
+    Auth(String),
+    #[error("network: {0}")]
+    Network(String),
+    #[error("mitigation: {0}")]
+    Mitigation(String),
+    #[error("http: {0}")]
+    Http(#[from] reqwest::Error),
+    /// A downloaded binary did not match its published checksum and was not installed.
+    #[error("checksum mismatch for {0}")]
+    Checksum(ChecksumVerification),
+    #[error("installing {0} was cancelled")]
+    InstallCancelled(String),
+}
+
+impl VscraperError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VscraperError::Io(_) => "io",
+            VscraperError::Tauri(_) => "tauri",
+            VscraperError::Json(_) => "json",
+            VscraperError::History(_) => "history",
+            VscraperError::Container(_) => "container",
+            VscraperError::InvalidAudioOptions(_) => "invalid_audio_options",
+            VscraperError::Ytdlp { .. } => "ytdlp",
+            VscraperError::DownloadNotFound(_) => "download_not_found",
+            VscraperError::Install(_) => "install",
+            VscraperError::Auth(_) => "auth",
+            VscraperError::Network(_) => "network",
+            VscraperError::Mitigation(_) => "mitigation",
+            VscraperError::Http(_) => "http",
+            VscraperError::Checksum(_) => "checksum",
+            VscraperError::InstallCancelled(_) => "install_cancelled",
+        }
+    }
+}
+
+/// Serialized as `{ "kind": "..", "message": ".." }` so the UI can branch on `kind`.
+impl Serialize for VscraperError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("VscraperError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
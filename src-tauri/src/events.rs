@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::Runtime;
+use tauri_plugin_log::log::{debug, error, info};
+use tokio::sync::mpsc;
+
+use crate::emissions::{Emission, EventEnvelope};
+use crate::emit_and_handle_result;
+
+/// Receives the events the download engine reports, so the engine runs the same under
+/// the GUI, which forwards them to the webview, and the CLI, which draws them in the terminal.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, emission: Emission, payload: serde_json::Value);
+}
+
+impl dyn EventSink + '_ {
+    /// Serializes `payload` and emits it.
+    pub fn send<T: Serialize>(&self, emission: Emission, payload: T) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.emit(emission, payload),
+            Err(err) => error!("serializing event: {}, err: {}", emission.as_string(), err),
+        }
+    }
+}
+
+impl<R: Runtime> EventSink for tauri::AppHandle<R> {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        emit_and_handle_result(self, emission, EventEnvelope::new(payload));
+    }
+}
+
+/// Sink forwarding engine events to the webview of `app_handle`.
+pub fn tauri_sink<R: Runtime>(app_handle: &tauri::AppHandle<R>) -> Arc<dyn EventSink> {
+    Arc::new(app_handle.clone())
+}
+
+/// Forwards events over a channel, for consumers that handle them on their own task.
+pub struct ChannelSink {
+    sender: mpsc::UnboundedSender<(Emission, serde_json::Value)>,
+}
+
+impl ChannelSink {
+    pub fn new() -> (ChannelSink, mpsc::UnboundedReceiver<(Emission, serde_json::Value)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (ChannelSink { sender }, receiver)
+    }
+}
+
+impl EventSink for ChannelSink {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        if self.sender.send((emission, payload)).is_err() {
+            debug!("dropped event: {}, receiver closed", emission.as_string());
+        }
+    }
+}
+
+/// Writes events to the log, progress updates only at debug level since they arrive
+/// several times a second.
+pub struct LoggingSink;
+
+impl EventSink for LoggingSink {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        match emission {
+            Emission::YtdlpDownloadUpdate | Emission::InstallProgress => {
+                debug!("{}: {}", emission.as_string(), payload)
+            }
+            _ => info!("{}: {}", emission.as_string(), payload),
+        }
+    }
+}
+
+/// Keeps every event in order so tests can assert on what the engine reported.
+#[cfg(test)]
+#[derive(Default)]
+pub struct RecorderSink {
+    events: std::sync::Mutex<Vec<(Emission, serde_json::Value)>>,
+}
+
+#[cfg(test)]
+impl RecorderSink {
+    pub fn events(&self) -> Vec<(Emission, serde_json::Value)> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Payloads of every `emission` event, oldest first.
+    pub fn payloads(&self, emission: Emission) -> Vec<serde_json::Value> {
+        self.events()
+            .into_iter()
+            .filter(|(recorded, _)| *recorded == emission)
+            .map(|(_, payload)| payload)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+impl EventSink for RecorderSink {
+    fn emit(&self, emission: Emission, payload: serde_json::Value) {
+        self.events.lock().unwrap().push((emission, payload));
+    }
+}
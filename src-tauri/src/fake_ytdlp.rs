@@ -0,0 +1,355 @@
+//! Scripted stand-in for yt-dlp, so the download engine can be tested offline.
+//!
+//! Each test gets a config directory whose yt-dlp is a shell script replaying a
+//! transcript from `testdata/ytdlp`, one directive per line:
+//!
+//! - `stdout <line>` / `stderr <line>` print the line.
+//! - `sleep <seconds>` pauses the replay.
+//! - `exit <code>` stops the replay with `code`, the default is `0`.
+//! - `hang` blocks until the process is killed.
+//! - `simulate <code>` fails the `--simulate` availability check with `code`, printing
+//!   the transcript's stderr lines. Without it the check succeeds silently.
+//!
+//! Lines starting with `#` are comments. Every invocation's arguments are appended to
+//! `invocations` next to the script.
+//!
+//! Transcripts print each line on the stream real yt-dlp uses: `--print` makes it quiet,
+//! so only the printed path goes to stdout, progress and log lines go to stderr.
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::config::CONFIG_FILENAME;
+use crate::emissions::Emission;
+use crate::engine::DownloadEngine;
+use crate::events::RecorderSink;
+use crate::queue::{DownloadQueue, JobId, JobState};
+use crate::retry::RetryPolicy;
+use crate::testing::TempDir;
+use crate::ytdlp::DownloadOptions;
+
+const FAKE_YTDLP_SCRIPT: &str = r#"#!/bin/sh
+dir=$(dirname "$0")
+transcript="$dir/transcript"
+echo "$*" >> "$dir/invocations"
+
+for arg in "$@"; do
+    if [ "$arg" = "--simulate" ]; then
+        code=$(sed -n 's/^simulate //p' "$transcript")
+        [ "${code:-0}" = 0 ] && exit 0
+        sed -n 's/^stderr //p' "$transcript" >&2
+        exit "$code"
+    fi
+done
+
+while IFS= read -r line; do
+    case $line in
+        "stdout "*) printf '%s\n' "${line#stdout }" ;;
+        "stderr "*) printf '%s\n' "${line#stderr }" >&2 ;;
+        "sleep "*) sleep "${line#sleep }" ;;
+        "exit "*) exit "${line#exit }" ;;
+        hang) exec sleep 3600 ;;
+    esac
+done < "$transcript"
+"#;
+
+const FAKE_FFMPEG_SCRIPT: &str = "#!/bin/sh\nexit 0\n";
+
+/// URL every fake download is queued under, the script ignores it.
+pub const FAKE_URL: &str = "https://www.youtube.com/watch?v=fake";
+
+/// How often tests check on the engine.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long tests wait for the engine before failing, so a stuck job fails the test
+/// instead of hanging it.
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct FakeYtdlp {
+    dir: TempDir,
+}
+
+impl FakeYtdlp {
+    /// Sets up a config directory whose yt-dlp replays `testdata/ytdlp/<transcript>.transcript`.
+    pub fn new(transcript: &str) -> FakeYtdlp {
+        let dir = TempDir::new(&format!("fake-ytdlp-{}", transcript));
+        fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/ytdlp").join(format!("{}.transcript", transcript)),
+            dir.join("transcript"),
+        )
+        .unwrap();
+
+        let ytdlp_path = dir.join("yt-dlp");
+        let ffmpeg_path = dir.join("ffmpeg");
+        for (path, script) in [(&ytdlp_path, FAKE_YTDLP_SCRIPT), (&ffmpeg_path, FAKE_FFMPEG_SCRIPT)] {
+            fs::write(path, script).unwrap();
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let config = serde_json::json!({
+            "ytdlp_path": ytdlp_path,
+            "ffmpeg_path": ffmpeg_path,
+            "history_path": ":memory:",
+            // Tests opt into retries per job, so failures are reported right away.
+            "retry": { "max_attempts": 1 },
+        });
+        fs::write(dir.join(CONFIG_FILENAME), config.to_string()).unwrap();
+
+        FakeYtdlp { dir }
+    }
+
+    /// An engine downloading into the fake's directory, with a recorder for its events.
+    pub fn engine(&self) -> (DownloadEngine, Arc<RecorderSink>) {
+        self.engine_with(DownloadQueue::default())
+    }
+
+    /// Like [`FakeYtdlp::engine`], running the jobs of `queue`.
+    pub fn engine_with(&self, queue: DownloadQueue) -> (DownloadEngine, Arc<RecorderSink>) {
+        let state = AppState::init(&self.dir, &self.dir, self.dir.to_path_buf(), queue).unwrap();
+        let recorder = Arc::new(RecorderSink::default());
+        (DownloadEngine::new(Arc::new(Mutex::new(state)), recorder.clone()), recorder)
+    }
+
+    /// Arguments of every run of the script, oldest first.
+    pub fn invocations(&self) -> Vec<String> {
+        fs::read_to_string(self.dir.join("invocations"))
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+}
+
+pub fn fake_options() -> DownloadOptions {
+    DownloadOptions::for_url(FAKE_URL)
+}
+
+/// Waits until `recorder` holds the end of the job `id`, and returns the state it ended in.
+pub async fn wait_for_job(recorder: &RecorderSink, id: &JobId) -> JobState {
+    let finished = tokio::time::timeout(WAIT_TIMEOUT, async {
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let finished = recorder.payloads(Emission::YtdlpDownloadFinish);
+            if let Some(finished) = finished.into_iter().find(|finished| finished["job_id"] == id.to_string()) {
+                return serde_json::from_value(finished["state"].clone()).unwrap();
+            }
+        }
+    })
+    .await;
+    match finished {
+        Ok(job_state) => job_state,
+        Err(_) => panic!("job {} did not end within {:?}, last event: {:?}", id, WAIT_TIMEOUT, recorder.events().pop()),
+    }
+}
+
+/// Waits until `recorder` holds at least one `emission` event.
+pub async fn wait_for_event(recorder: &RecorderSink, emission: Emission) {
+    let received = tokio::time::timeout(WAIT_TIMEOUT, async {
+        while recorder.payloads(emission).is_empty() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+    .await;
+    if received.is_err() {
+        panic!(
+            "no {} event within {:?}, last event: {:?}",
+            emission.as_string(),
+            WAIT_TIMEOUT,
+            recorder.events().pop()
+        );
+    }
+}
+
+#[test]
+fn test_fake_download_reports_progress() {
+    let fake = FakeYtdlp::new("progress");
+    let (engine, recorder) = fake.engine();
+    let (id, job_state, history) = tauri::async_runtime::block_on(async {
+        let id = engine.enqueue(fake_options()).await.unwrap();
+        let job_state = wait_for_job(&recorder, &id).await;
+        (id, job_state, engine.state().lock().await.history().page(0, 10).unwrap())
+    });
+
+    assert_eq!(job_state, JobState::Finished);
+    let finished = recorder.payloads(Emission::YtdlpDownloadFinish);
+    assert_eq!(finished.len(), 1);
+    assert_eq!(finished[0]["job_id"], id.to_string());
+    assert_eq!(finished[0]["state"], "finished");
+    assert_eq!(finished[0]["output_path"], "/downloads/fake.mp4");
+    assert_eq!(finished[0]["error"], serde_json::Value::Null);
+    assert_eq!(recorder.payloads(Emission::YtdlpUrlUpdate)[0]["available"], true);
+    assert!(recorder.payloads(Emission::YtdlpDownloadError).is_empty());
+
+    let phases: Vec<serde_json::Value> = recorder
+        .payloads(Emission::YtdlpDownloadUpdate)
+        .into_iter()
+        .map(|progress| {
+            assert_eq!(progress["job_id"], id.to_string());
+            progress["phase"].clone()
+        })
+        .collect();
+    assert_eq!(phases, ["downloading", "downloading", "downloading", "merging"]);
+
+    assert_eq!(serde_json::to_value(&history[0]).unwrap()["output_path"], "/downloads/fake.mp4");
+
+    // The availability check, then the download.
+    let invocations = fake.invocations();
+    assert_eq!(invocations.len(), 2);
+    assert!(invocations[0].contains("--simulate"));
+    assert!(invocations[1].contains("--newline") && invocations[1].ends_with(FAKE_URL));
+}
+
+#[test]
+fn test_fake_download_classifies_failures() {
+    // Rate limited downloads are retried with the `gentle`, then the `strict` profile.
+    for (transcript, error, invocations) in [("unavailable", "unavailable", 1), ("rate_limited", "rate_limited", 4)] {
+        let fake = FakeYtdlp::new(transcript);
+        let (engine, recorder) = fake.engine();
+        let job_state = tauri::async_runtime::block_on(async {
+            let id = engine.enqueue(fake_options()).await.unwrap();
+            wait_for_job(&recorder, &id).await
+        });
+
+        assert_eq!(job_state, JobState::Failed, "{}", transcript);
+        let failures = recorder.payloads(Emission::YtdlpDownloadError);
+        assert_eq!(failures.len(), 1, "{}", transcript);
+        assert_eq!(failures[0]["error"], error);
+        assert_eq!(failures[0]["exit_code"], 1);
+        let stderr_tail = failures[0]["stderr_tail"].as_array().unwrap();
+        assert!(stderr_tail.last().unwrap().as_str().unwrap().starts_with("ERROR:"));
+        assert_eq!(recorder.payloads(Emission::YtdlpDownloadFinish)[0]["error"], error);
+        let invocations_made = fake.invocations();
+        assert_eq!(invocations_made.len(), invocations, "{}", transcript);
+        if transcript == "rate_limited" {
+            assert!(!invocations_made[1].contains("--sleep-requests"));
+            assert!(invocations_made[2].contains("--sleep-requests 1 ") && invocations_made[2].contains("--continue"));
+            assert!(invocations_made[3].contains("--limit-rate 2M"));
+        }
+    }
+}
+
+#[test]
+fn test_fake_download_reads_progress_from_stderr() {
+    let fake = FakeYtdlp::new("rate_limited");
+    let (engine, recorder) = fake.engine();
+    tauri::async_runtime::block_on(async {
+        let id = engine.enqueue(fake_options()).await.unwrap();
+        wait_for_job(&recorder, &id).await
+    });
+
+    let progress = recorder.payloads(Emission::YtdlpDownloadUpdate);
+    assert!(!progress.is_empty());
+    assert_eq!(progress[0]["downloaded_bytes"], 512);
+    // Progress lines are reported, not kept as diagnostics.
+    let failures = recorder.payloads(Emission::YtdlpDownloadError);
+    assert_eq!(
+        failures[0]["stderr_tail"],
+        serde_json::json!([
+            "[youtube] Extracting URL: https://www.youtube.com/watch?v=fake",
+            "ERROR: unable to download video data: HTTP Error 429: Too Many Requests",
+        ])
+    );
+}
+
+#[test]
+fn test_fake_download_retries_with_backoff() {
+    let fake = FakeYtdlp::new("rate_limited");
+    let (engine, recorder) = fake.engine();
+    let retry: RetryPolicy = serde_json::from_str(r#"{ "max_attempts": 2, "initial_delay_ms": 10 }"#).unwrap();
+    let job_state = tauri::async_runtime::block_on(async {
+        let id = engine.enqueue(fake_options().with_retry(Some(retry))).await.unwrap();
+        wait_for_job(&recorder, &id).await
+    });
+
+    assert_eq!(job_state, JobState::Failed);
+    // The check, three runs escalating to the `strict` profile, then one retry.
+    assert_eq!(fake.invocations().len(), 5);
+    let attempts: Vec<serde_json::Value> = recorder
+        .payloads(Emission::YtdlpDownloadUpdate)
+        .into_iter()
+        .map(|progress| progress["attempt"].clone())
+        .collect();
+    assert_eq!(attempts, [1, 1, 1, 2]);
+    assert_eq!(recorder.payloads(Emission::YtdlpDownloadError).len(), 1);
+}
+
+#[test]
+fn test_fake_download_starts_restored_queue() {
+    let fake = FakeYtdlp::new("hang");
+    let queue_dir = TempDir::new("restored-queue");
+    let id = DownloadQueue::load(&queue_dir).enqueue(fake_options(), None);
+    let (engine, recorder) = fake.engine_with(DownloadQueue::load(&queue_dir));
+    let job_state = tauri::async_runtime::block_on(async {
+        // What the app does once its state is set up.
+        engine.schedule();
+        wait_for_event(&recorder, Emission::YtdlpDownloadUpdate).await;
+        let job_state = engine.state().lock().await.queue().get(&id).unwrap().state();
+        engine.cancel(id.clone()).await.unwrap();
+        wait_for_job(&recorder, &id).await;
+        job_state
+    });
+
+    assert_eq!(job_state, JobState::Running);
+}
+
+#[test]
+fn test_fake_download_pauses_while_waiting() {
+    let fake = FakeYtdlp::new("progress");
+    let mut queue = DownloadQueue::default();
+    // The `gentle` profile waits 3 seconds before each playlist entry.
+    let options = fake_options().with_mitigation(Some(String::from("gentle")));
+    let id = queue.enqueue(options, Some(String::from("playlist")));
+    let (engine, recorder) = fake.engine_with(queue);
+    let (paused_state, job_state) = tauri::async_runtime::block_on(async {
+        engine.schedule();
+        wait_for_event(&recorder, Emission::YtdlpPlaylistUpdate).await;
+        assert!(engine.pause(&id).await);
+        wait_for_event(&recorder, Emission::YtdlpPauseDownload).await;
+        let paused_state = engine.state().lock().await.queue().get(&id).unwrap().state();
+        // Resuming skips the rest of the wait.
+        assert!(engine.resume(&id).await);
+        (paused_state, wait_for_job(&recorder, &id).await)
+    });
+
+    assert_eq!(paused_state, JobState::Paused);
+    assert_eq!(job_state, JobState::Finished);
+    assert_eq!(recorder.payloads(Emission::YtdlpResumeDownload)[0]["state"], "running");
+    assert_eq!(fake.invocations().len(), 2);
+}
+
+#[test]
+fn test_fake_download_interrupts_on_shutdown() {
+    let fake = FakeYtdlp::new("hang");
+    let (engine, recorder) = fake.engine();
+    let job = tauri::async_runtime::block_on(async {
+        let id = engine.enqueue(fake_options()).await.unwrap();
+        wait_for_event(&recorder, Emission::YtdlpDownloadUpdate).await;
+        engine.shutdown(Duration::from_secs(10)).await;
+        engine.state().lock().await.queue().get(&id).cloned().unwrap()
+    });
+
+    assert_eq!(job.state(), JobState::Interrupted);
+    assert!(job.is_resume());
+    assert_eq!(recorder.payloads(Emission::YtdlpDownloadFinish)[0]["state"], "interrupted");
+}
+
+#[test]
+fn test_fake_download_cancels_hung_child() {
+    let fake = FakeYtdlp::new("hang");
+    let (engine, recorder) = fake.engine();
+    let job_state = tauri::async_runtime::block_on(async {
+        let id = engine.enqueue(fake_options()).await.unwrap();
+        wait_for_event(&recorder, Emission::YtdlpDownloadUpdate).await;
+        engine.cancel(id.clone()).await.unwrap();
+        wait_for_job(&recorder, &id).await
+    });
+
+    assert_eq!(job_state, JobState::Cancelled);
+    assert_eq!(recorder.payloads(Emission::YtdlpCancelDownload)[0]["state"], "cancelled");
+    assert_eq!(recorder.payloads(Emission::YtdlpDownloadFinish)[0]["state"], "cancelled");
+}
@@ -0,0 +1,245 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::engine::DownloadEngine;
+use crate::error::VscraperError;
+use crate::queue::{JobId, JobState};
+use crate::ytdlp::DownloadOptions;
+
+const HISTORY_SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        url TEXT NOT NULL,
+        options TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        finished_at INTEGER,
+        state TEXT NOT NULL,
+        exit_code INTEGER,
+        output_path TEXT,
+        file_size INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS history_started_at ON history (started_at);
+";
+
+const HISTORY_COLUMNS: &str =
+    "id, url, options, started_at, finished_at, state, exit_code, output_path, file_size";
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HistoryEntry {
+    id: i64,
+    url: String,
+    options: DownloadOptions,
+    /// Unix timestamp in seconds.
+    started_at: i64,
+    finished_at: Option<i64>,
+    state: JobState,
+    exit_code: Option<i32>,
+    output_path: Option<PathBuf>,
+    file_size: Option<u64>,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<HistoryEntry> {
+        let options: String = row.get("options")?;
+        let state: String = row.get("state")?;
+        let output_path: Option<String> = row.get("output_path")?;
+        Ok(HistoryEntry {
+            id: row.get("id")?,
+            url: row.get("url")?,
+            options: serde_json::from_str(&options).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(err))
+            })?,
+            started_at: row.get("started_at")?,
+            finished_at: row.get("finished_at")?,
+            state: serde_json::from_value(serde_json::Value::String(state)).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(5, rusqlite::types::Type::Text, Box::new(err))
+            })?,
+            exit_code: row.get("exit_code")?,
+            output_path: output_path.map(PathBuf::from),
+            file_size: row.get("file_size")?,
+        })
+    }
+
+    pub fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+}
+
+/// Record of every job started through the queue, kept in the bundled SQLite database.
+pub struct HistoryStore {
+    connection: Connection,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> rusqlite::Result<HistoryStore> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(HISTORY_SCHEMA)?;
+        Ok(HistoryStore { connection })
+    }
+
+    /// Inserts a running entry for a job and returns its id.
+    pub fn record_start(&self, options: &DownloadOptions) -> rusqlite::Result<i64> {
+        let options_as_str = serde_json::to_string(options)
+            .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+        self.connection.execute(
+            "INSERT INTO history (url, options, started_at, state) VALUES (?1, ?2, ?3, ?4)",
+            params![options.url(), options_as_str, unix_now(), JobState::Running.as_str()],
+        )?;
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    pub fn record_finish(
+        &self,
+        id: i64,
+        state: JobState,
+        exit_code: Option<i32>,
+        output_path: Option<&Path>,
+    ) -> rusqlite::Result<()> {
+        let file_size = output_path
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len());
+        self.connection.execute(
+            "UPDATE history
+             SET finished_at = ?2, state = ?3, exit_code = ?4, output_path = ?5, file_size = ?6
+             WHERE id = ?1",
+            params![
+                id,
+                unix_now(),
+                state.as_str(),
+                exit_code,
+                output_path.map(|path| path.to_string_lossy().into_owned()),
+                file_size,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn get(&self, id: i64) -> rusqlite::Result<Option<HistoryEntry>> {
+        self.connection
+            .query_row(
+                &format!("SELECT {} FROM history WHERE id = ?1", HISTORY_COLUMNS),
+                params![id],
+                HistoryEntry::from_row,
+            )
+            .optional()
+    }
+
+    /// Returns entries newest first, `page` is zero based.
+    pub fn page(&self, page: u32, page_size: u32) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM history ORDER BY started_at DESC, id DESC LIMIT ?1 OFFSET ?2",
+            HISTORY_COLUMNS
+        ))?;
+        let entries = statement
+            .query_map(params![page_size, page_offset(page, page_size)], HistoryEntry::from_row)?
+            .collect();
+        entries
+    }
+
+    /// Like [`HistoryStore::page`], limited to entries whose URL or output path contains `query`.
+    pub fn search(&self, query: &str, page: u32, page_size: u32) -> rusqlite::Result<Vec<HistoryEntry>> {
+        let pattern = format!(
+            "%{}%",
+            query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        let mut statement = self.connection.prepare(&format!(
+            "SELECT {} FROM history
+             WHERE url LIKE ?1 ESCAPE '\\' OR output_path LIKE ?1 ESCAPE '\\'
+             ORDER BY started_at DESC, id DESC LIMIT ?2 OFFSET ?3",
+            HISTORY_COLUMNS
+        ))?;
+        let entries = statement
+            .query_map(params![pattern, page_size, page_offset(page, page_size)], HistoryEntry::from_row)?
+            .collect();
+        entries
+    }
+
+    pub fn delete(&self, id: i64) -> rusqlite::Result<bool> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM history WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+}
+
+/// Rows skipped before `page`. Both come from the frontend, so the product saturates
+/// instead of overflowing, pages past the end are simply empty.
+fn page_offset(page: u32, page_size: u32) -> i64 {
+    i64::from(page).saturating_mul(i64::from(page_size))
+}
+
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+#[tauri::command]
+pub async fn list_history(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    page: u32,
+    page_size: u32,
+) -> Result<Vec<HistoryEntry>, VscraperError> {
+    Ok(state.lock().await.history().page(page, page_size)?)
+}
+
+#[tauri::command]
+pub async fn search_history(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    query: String,
+    page: u32,
+    page_size: u32,
+) -> Result<Vec<HistoryEntry>, VscraperError> {
+    Ok(state.lock().await.history().search(&query, page, page_size)?)
+}
+
+#[tauri::command]
+pub async fn delete_history_entry(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    id: i64,
+) -> Result<bool, VscraperError> {
+    Ok(state.lock().await.history().delete(id)?)
+}
+
+/// Queues a new job with the same options as a past one, returns `None` if there is no such entry.
+#[tauri::command]
+pub async fn rerun_history_entry(
+    engine: State<'_, DownloadEngine>,
+    id: i64,
+) -> Result<Option<JobId>, VscraperError> {
+    let entry = engine.state().lock().await.history().get(id)?;
+    match entry {
+        Some(entry) => Ok(Some(engine.enqueue(entry.options().clone()).await?)),
+        None => Ok(None),
+    }
+}
+
+#[test]
+fn test_history_record_and_search() {
+    let history = HistoryStore::open(Path::new(":memory:")).unwrap();
+    let options = DownloadOptions::for_url("https://example.com/100%_real");
+
+    let id = history.record_start(&options).unwrap();
+    history.record_finish(id, JobState::Finished, Some(0), None).unwrap();
+    history.record_start(&DownloadOptions::for_url("https://example.com/other")).unwrap();
+
+    assert_eq!(history.page(0, 10).unwrap().len(), 2);
+    assert_eq!(history.page(1, 1).unwrap().len(), 1);
+    assert!(history.page(u32::MAX, u32::MAX).unwrap().is_empty());
+
+    let found = history.search("100%_", 0, 10).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].state, JobState::Finished);
+    assert_eq!(found[0].exit_code, Some(0));
+
+    assert!(history.delete(id).unwrap());
+    assert!(history.get(id).unwrap().is_none());
+}
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_log::log::warn;
+use tokio::sync::Mutex;
+use ts_rs::TS;
+
+use crate::app_state::AppState;
+use crate::checksum::{self, SHA256SUMS_ASSET, SHA256_SUFFIX};
+use crate::components::{self, FFMPEG_EXECUTABLE};
+use crate::emissions::InstallPhase;
+use crate::error::VscraperError;
+use crate::installs::InstallTask;
+use crate::network::NetworkSettings;
+use crate::versions::{self, GithubAsset, GithubRelease};
+
+/// File at the root of a mirror holding its newest tag.
+pub const MIRROR_LATEST: &str = "LATEST";
+
+/// Archives [`extract`] cannot unpack, a `.tar.gz` would be installed still tarred.
+const UNSUPPORTED_ARCHIVES: [&str; 7] = [".tar.gz", ".tgz", ".tar", ".xz", ".bz2", ".7z", ".rar"];
+
+/// Tag recorded for local installs without a pinned tag.
+pub const LOCAL_TAG: &str = "local";
+
+/// Where a component is installed from.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[ts(export)]
+pub enum InstallSource {
+    /// The project's GitHub releases.
+    #[default]
+    Github,
+    /// A copy of the GitHub release downloads serving `<base_url>/<tag>/<asset>`, the
+    /// release's checksum files next to the asset and the newest tag in `<base_url>/LATEST`.
+    Mirror { base_url: String },
+    /// A binary, or a `.gz` or `.zip` archive holding it, on this machine.
+    Local {
+        #[ts(type = "string")]
+        path: PathBuf,
+        /// Checked instead of a `SHA2-256SUMS` or `<file>.sha256` next to the file.
+        #[serde(default)]
+        sha256: Option<String>,
+    },
+}
+
+/// Install source of each component.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, TS)]
+#[ts(export)]
+pub struct InstallSources {
+    #[serde(default)]
+    ytdlp: InstallSource,
+    #[serde(default)]
+    ffmpeg: InstallSource,
+}
+
+impl InstallSources {
+    pub fn get(&self, executable_name: &str) -> &InstallSource {
+        match executable_name {
+            FFMPEG_EXECUTABLE => &self.ffmpeg,
+            _ => &self.ytdlp,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), VscraperError> {
+        self.ytdlp.validate()?;
+        self.ffmpeg.validate()
+    }
+}
+
+/// An asset fetched from an [`InstallSource`], still compressed when it is an archive.
+pub struct Fetched {
+    pub tag: String,
+    pub asset: String,
+    pub data: Vec<u8>,
+    /// sha256 published for the asset, `None` for local files without one.
+    pub expected_sha256: Option<String>,
+}
+
+impl InstallSource {
+    pub fn validate(&self) -> Result<(), VscraperError> {
+        match self {
+            InstallSource::Github => Ok(()),
+            InstallSource::Mirror { base_url } => match base_url.split_once("://") {
+                Some(("http" | "https", rest)) if !rest.is_empty() => Ok(()),
+                _ => Err(VscraperError::Install(format!(
+                    "unsupported mirror: {}, expected an http or https url",
+                    base_url
+                ))),
+            },
+            InstallSource::Local { path, sha256 } => {
+                if !path.is_file() {
+                    return Err(VscraperError::Install(format!("no file at {}", path.display())));
+                }
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+                if UNSUPPORTED_ARCHIVES.iter().any(|extension| file_name.ends_with(extension)) {
+                    return Err(VscraperError::Install(format!(
+                        "unsupported archive: {}, expected a binary, .gz or .zip",
+                        path.display()
+                    )));
+                }
+                match sha256.as_deref().map(checksum::parse_hash) {
+                    Some(None) => Err(VscraperError::Install(format!("invalid sha256 for {}", path.display()))),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// What installed versions are recorded under, so switching sources reinstalls.
+    pub fn repository(&self, project: &str) -> String {
+        match self {
+            InstallSource::Github => project.to_string(),
+            InstallSource::Mirror { base_url } => base_url.trim_end_matches('/').to_string(),
+            InstallSource::Local { path, .. } => path.display().to_string(),
+        }
+    }
+
+    /// The tag installs without a pinned tag get.
+    pub async fn latest_tag(&self, network: &NetworkSettings, project: &str) -> Result<String, VscraperError> {
+        match self {
+            InstallSource::Github => versions::latest_release_tag(network, project).await,
+            InstallSource::Mirror { base_url } => {
+                let url = format!("{}/{}", base_url.trim_end_matches('/'), MIRROR_LATEST);
+                let latest = download(network, &url)
+                    .await
+                    .map_err(|err| VscraperError::Install(format!("reading {}: {}", url, err)))?;
+                Ok(String::from_utf8_lossy(&latest).trim().to_string())
+            }
+            InstallSource::Local { .. } => Ok(String::from(LOCAL_TAG)),
+        }
+    }
+
+    /// Fetches release `tag`, the newest one when `None`, of the asset holding
+    /// `executable_name` for this platform, reporting the download to `task`.
+    pub async fn fetch(
+        &self,
+        network: &NetworkSettings,
+        project: &str,
+        executable_name: &str,
+        tag: Option<&str>,
+        task: &InstallTask,
+    ) -> Result<Fetched, VscraperError> {
+        task.phase(InstallPhase::ResolvingRelease)?;
+        match self {
+            InstallSource::Github => {
+                let release = versions::release(network, project, tag).await?;
+                let asset_name = components::release_asset(executable_name)?;
+                let asset = release.asset(&asset_name).ok_or_else(|| {
+                    VscraperError::Install(format!("{} {} has no asset {}", project, release.tag_name, asset_name))
+                })?;
+                let expected_sha256 = github_sha256(network, &release, asset).await?;
+                let data = download_tracked(network, &asset.browser_download_url, task).await?;
+                Ok(Fetched {
+                    tag: release.tag_name.clone(),
+                    asset: asset_name,
+                    data,
+                    expected_sha256: Some(expected_sha256),
+                })
+            }
+            InstallSource::Mirror { base_url } => {
+                let tag = match tag {
+                    Some(tag) => tag.to_string(),
+                    None => self.latest_tag(network, project).await?,
+                };
+                let dir = format!("{}/{}", base_url.trim_end_matches('/'), tag);
+                let asset = components::release_asset(executable_name)?;
+                let expected_sha256 = mirror_sha256(network, &dir, &asset).await?;
+                let data = download_tracked(network, &format!("{}/{}", dir, asset), task).await?;
+                Ok(Fetched {
+                    tag,
+                    asset,
+                    data,
+                    expected_sha256: Some(expected_sha256),
+                })
+            }
+            InstallSource::Local { path, sha256 } => {
+                self.validate()?;
+                let asset = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let expected_sha256 = match sha256 {
+                    Some(sha256) => checksum::parse_hash(sha256),
+                    None => local_sha256(path, &asset),
+                };
+                if expected_sha256.is_none() {
+                    warn!("no sha256 for {}, installing it unverified", path.display());
+                }
+                task.phase(InstallPhase::Downloading)?;
+                let data = fs::read(path)?;
+                task.downloaded(data.len() as u64, Some(data.len() as u64))?;
+                Ok(Fetched {
+                    tag: tag.unwrap_or(LOCAL_TAG).to_string(),
+                    asset,
+                    data,
+                    expected_sha256,
+                })
+            }
+        }
+    }
+}
+
+async fn download(network: &NetworkSettings, url: &str) -> Result<Vec<u8>, VscraperError> {
+    let response = network.http_client(url)?.get(url).send().await?.error_for_status()?;
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// Like [`download`], reporting the bytes received to `task` and stopping once it is cancelled.
+async fn download_tracked(network: &NetworkSettings, url: &str, task: &InstallTask) -> Result<Vec<u8>, VscraperError> {
+    let failed = |err: reqwest::Error| VscraperError::Install(format!("downloading {}: {}", url, err));
+    task.phase(InstallPhase::Downloading)?;
+    let mut response = network
+        .http_client(url)?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(failed)?;
+    let total_bytes = response.content_length();
+    let mut data = Vec::with_capacity(total_bytes.unwrap_or_default() as usize);
+    task.downloaded(0, total_bytes)?;
+    while let Some(chunk) = response.chunk().await.map_err(failed)? {
+        data.extend_from_slice(&chunk);
+        task.downloaded(data.len() as u64, total_bytes)?;
+    }
+    Ok(data)
+}
+
+pub fn is_archive(asset: &str) -> bool {
+    asset.ends_with(".gz") || asset.ends_with(".zip")
+}
+
+/// The sha256 `release` publishes for `asset`, from its `SHA2-256SUMS` file, the asset's
+/// own `.sha256` file or the digest GitHub computed, in that order.
+async fn github_sha256(
+    network: &NetworkSettings,
+    release: &GithubRelease,
+    asset: &GithubAsset,
+) -> Result<String, VscraperError> {
+    let hash = if let Some(sums) = release.asset(SHA256SUMS_ASSET) {
+        let sums = download(network, &sums.browser_download_url).await?;
+        checksum::parse_sums(&String::from_utf8_lossy(&sums)).remove(&asset.name)
+    } else if let Some(hash_file) = release.asset(&format!("{}{}", asset.name, SHA256_SUFFIX)) {
+        checksum::parse_hash(&String::from_utf8_lossy(&download(network, &hash_file.browser_download_url).await?))
+    } else {
+        asset.digest.as_deref().and_then(checksum::parse_hash)
+    };
+    hash.ok_or_else(|| {
+        VscraperError::Install(format!("release {} publishes no sha256 for {}", release.tag_name, asset.name))
+    })
+}
+
+/// Like [`github_sha256`] for the release directory `dir` of a mirror, which has no digests.
+async fn mirror_sha256(network: &NetworkSettings, dir: &str, asset: &str) -> Result<String, VscraperError> {
+    if let Ok(sums) = download(network, &format!("{}/{}", dir, SHA256SUMS_ASSET)).await {
+        if let Some(hash) = checksum::parse_sums(&String::from_utf8_lossy(&sums)).remove(asset) {
+            return Ok(hash);
+        }
+    }
+    download(network, &format!("{}/{}{}", dir, asset, SHA256_SUFFIX))
+        .await
+        .ok()
+        .and_then(|hash_file| checksum::parse_hash(&String::from_utf8_lossy(&hash_file)))
+        .ok_or_else(|| VscraperError::Install(format!("mirror {} publishes no sha256 for {}", dir, asset)))
+}
+
+/// The sha256 a `SHA2-256SUMS` or `<asset>.sha256` next to the local file `path` lists.
+fn local_sha256(path: &Path, asset: &str) -> Option<String> {
+    let dir = path.parent()?;
+    fs::read_to_string(dir.join(SHA256SUMS_ASSET))
+        .ok()
+        .and_then(|sums| checksum::parse_sums(&sums).remove(asset))
+        .or_else(|| {
+            let hash_file = fs::read_to_string(dir.join(format!("{}{}", asset, SHA256_SUFFIX))).ok()?;
+            checksum::parse_hash(&hash_file)
+        })
+}
+
+/// The binary named `executable_name` inside the archive `asset`, or `data` itself when
+/// the asset is not an archive.
+pub fn extract(asset: &str, data: Vec<u8>, executable_name: &str) -> Result<Vec<u8>, VscraperError> {
+    if !is_archive(asset) {
+        return Ok(data);
+    }
+    if asset.ends_with(".gz") {
+        let mut binary = Vec::new();
+        flate2::read::GzDecoder::new(data.as_slice()).read_to_end(&mut binary)?;
+        return Ok(binary);
+    }
+
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(data))
+        .map_err(|err| VscraperError::Install(format!("reading {}: {}", asset, err)))?;
+    let executable_file = format!("{}{}", executable_name, std::env::consts::EXE_SUFFIX);
+    let entry = (0..archive.len())
+        .find(|&index| {
+            archive.by_index(index).is_ok_and(|entry| {
+                entry.is_file() && Path::new(entry.name()).file_name().is_some_and(|name| name == executable_file.as_str())
+            })
+        })
+        .ok_or_else(|| VscraperError::Install(format!("{} holds no {}", asset, executable_file)))?;
+    let mut binary = Vec::new();
+    archive
+        .by_index(entry)
+        .map_err(|err| VscraperError::Install(format!("reading {}: {}", asset, err)))?
+        .read_to_end(&mut binary)?;
+    Ok(binary)
+}
+
+#[tauri::command]
+pub async fn update_install_sources(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    install_sources: InstallSources,
+) -> Result<(), VscraperError> {
+    install_sources.validate()?;
+    state.lock().await.set_install_sources(install_sources);
+    Ok(())
+}
+
+#[test]
+fn test_extract_archives() {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut gz = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gz.write_all(b"ffmpeg binary").unwrap();
+    let gz = gz.finish().unwrap();
+    assert_eq!(extract("ffmpeg-linux-x64.gz", gz, FFMPEG_EXECUTABLE).unwrap(), b"ffmpeg binary");
+
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("README.txt", zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"readme").unwrap();
+    zip.start_file("yt-dlp/yt-dlp", zip::write::SimpleFileOptions::default()).unwrap();
+    zip.write_all(b"yt-dlp binary").unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+    assert_eq!(extract("yt-dlp.zip", zip.clone(), "yt-dlp").unwrap(), b"yt-dlp binary");
+    assert!(extract("yt-dlp.zip", zip, FFMPEG_EXECUTABLE).is_err());
+
+    assert_eq!(extract("yt-dlp_linux", b"raw".to_vec(), "yt-dlp").unwrap(), b"raw");
+}
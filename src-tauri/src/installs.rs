@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+use tauri_plugin_log::log::{error, info};
+
+use crate::components::Installed;
+use crate::emissions::{Emission, InstallFinished, InstallPhase, InstallProgress};
+use crate::error::VscraperError;
+use crate::events::EventSink;
+
+/// How often download progress is reported, ffmpeg arrives in thousands of chunks.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// One running install, reporting its progress and whether it was cancelled.
+pub struct InstallTask {
+    events: Arc<dyn EventSink>,
+    cancelled: AtomicBool,
+    /// The progress and when it was last reported.
+    progress: std::sync::Mutex<(InstallProgress, Option<Instant>)>,
+}
+
+impl InstallTask {
+    pub fn new(executable_name: &str, events: Arc<dyn EventSink>) -> InstallTask {
+        InstallTask {
+            events,
+            cancelled: AtomicBool::new(false),
+            progress: std::sync::Mutex::new((InstallProgress::new(executable_name), None)),
+        }
+    }
+
+    pub fn progress(&self) -> InstallProgress {
+        self.progress.lock().unwrap_or_else(|err| err.into_inner()).0.clone()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Fails once the install was cancelled, installs check before every step.
+    pub fn check_cancelled(&self) -> Result<(), VscraperError> {
+        match self.cancelled.load(Ordering::SeqCst) {
+            true => Err(VscraperError::InstallCancelled(self.progress().executable().to_string())),
+            false => Ok(()),
+        }
+    }
+
+    pub fn phase(&self, phase: InstallPhase) -> Result<(), VscraperError> {
+        self.check_cancelled()?;
+        self.report(|progress| progress.set_phase(phase), true);
+        Ok(())
+    }
+
+    pub fn downloaded(&self, downloaded_bytes: u64, total_bytes: Option<u64>) -> Result<(), VscraperError> {
+        self.check_cancelled()?;
+        let done = Some(downloaded_bytes) == total_bytes;
+        self.report(|progress| progress.set_bytes(downloaded_bytes, total_bytes), done);
+        Ok(())
+    }
+
+    /// Applies `update` and reports the progress when `force`d or due.
+    fn report(&self, update: impl FnOnce(&mut InstallProgress), force: bool) {
+        let progress = {
+            let mut guard = self.progress.lock().unwrap_or_else(|err| err.into_inner());
+            let (progress, last_reported) = &mut *guard;
+            update(progress);
+            if !force && last_reported.is_some_and(|last_reported| last_reported.elapsed() < PROGRESS_INTERVAL) {
+                return;
+            }
+            *last_reported = Some(Instant::now());
+            progress.clone()
+        };
+        self.events.send(Emission::InstallProgress, progress);
+    }
+}
+
+/// Runs component installs, one per component at a time, so they can be listed and
+/// cancelled. Cloning is cheap, clones share the same tasks and sink.
+#[derive(Clone)]
+pub struct InstallManager {
+    events: Arc<dyn EventSink>,
+    tasks: Arc<std::sync::Mutex<HashMap<String, Arc<InstallTask>>>>,
+}
+
+impl InstallManager {
+    pub fn new(events: Arc<dyn EventSink>) -> InstallManager {
+        InstallManager {
+            events,
+            tasks: Arc::default(),
+        }
+    }
+
+    fn tasks(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<InstallTask>>> {
+        self.tasks.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Runs `install` in the background, reporting its outcome as `emission`.
+    pub fn start<F, Fut>(&self, executable_name: &str, emission: Emission, install: F) -> Result<(), VscraperError>
+    where
+        F: FnOnce(Arc<InstallTask>) -> Fut,
+        Fut: Future<Output = Result<Installed, VscraperError>> + Send + 'static,
+    {
+        let task = Arc::new(InstallTask::new(executable_name, self.events.clone()));
+        {
+            let mut tasks = self.tasks();
+            if tasks.contains_key(executable_name) {
+                return Err(VscraperError::Install(format!("{} is already being installed", executable_name)));
+            }
+            tasks.insert(executable_name.to_string(), task.clone());
+        }
+
+        let install = install(task);
+        let manager = self.clone();
+        let executable_name = executable_name.to_string();
+        tauri::async_runtime::spawn(async move {
+            let install_result = install.await;
+            manager.tasks().remove(&executable_name);
+            match &install_result {
+                Err(VscraperError::InstallCancelled(_)) => info!("cancelled installing {}", executable_name),
+                Err(err) => error!("{}", err),
+                Ok(_) => {}
+            }
+            manager.events.send(emission, InstallFinished::new(&executable_name, &install_result));
+        });
+        Ok(())
+    }
+
+    /// Stops the install of `executable_name` at its next step, the installed binary is
+    /// only replaced in the last one.
+    pub fn cancel(&self, executable_name: &str) -> Result<(), VscraperError> {
+        match self.tasks().get(executable_name) {
+            Some(task) => {
+                task.cancel();
+                Ok(())
+            }
+            None => Err(VscraperError::Install(format!("{} is not being installed", executable_name))),
+        }
+    }
+
+    pub fn list(&self) -> Vec<InstallProgress> {
+        self.tasks().values().map(|task| task.progress()).collect()
+    }
+}
+
+#[tauri::command]
+pub async fn cancel_install(installs: State<'_, InstallManager>, executable: String) -> Result<(), VscraperError> {
+    installs.cancel(&executable)
+}
+
+#[tauri::command]
+pub async fn list_installs(installs: State<'_, InstallManager>) -> Result<Vec<InstallProgress>, VscraperError> {
+    Ok(installs.list())
+}
+
+#[test]
+fn test_install_task_is_cancelled() {
+    use crate::events::RecorderSink;
+
+    let recorder = Arc::new(RecorderSink::default());
+    let manager = InstallManager::new(recorder.clone());
+    tauri::async_runtime::block_on(async {
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        manager
+            .start("ffmpeg", Emission::FfmpegInstall, |task| async move {
+                task.phase(InstallPhase::Downloading)?;
+                for downloaded in [0, 512, 1024] {
+                    task.downloaded(downloaded, Some(2048))?;
+                }
+                let _ = released.await;
+                task.downloaded(2048, Some(2048))?;
+                Ok(Installed::up_to_date(String::from("b6.0")))
+            })
+            .unwrap();
+        assert!(manager.start("ffmpeg", Emission::FfmpegInstall, |_| async { unreachable!() }).is_err());
+        assert_eq!(manager.list().len(), 1);
+
+        while recorder.payloads(Emission::InstallProgress).is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        manager.cancel("ffmpeg").unwrap();
+        release.send(()).unwrap();
+        while recorder.payloads(Emission::FfmpegInstall).is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    });
+
+    // Byte counts right after the phase change are throttled.
+    let progress = recorder.payloads(Emission::InstallProgress);
+    assert_eq!(progress.len(), 1);
+    assert_eq!(progress[0]["phase"], "downloading");
+    let finished = recorder.payloads(Emission::FfmpegInstall);
+    assert_eq!(finished[0]["error"]["kind"], "install_cancelled");
+    assert!(manager.cancel("ffmpeg").is_err());
+}
@@ -1,148 +1,237 @@
-use serde::Serialize;
-use tauri::{Emitter, Runtime};
-use tauri_plugin_log::log::{error, trace};
-use tokio::sync::Mutex;
-use std::{
-    fs, sync::Arc,
-};
-use clap::Parser;
-use tauri::{Manager, State, WindowEvent};
-use tauri_plugin_log::log::{self, LevelFilter};
-use tauri_plugin_window_state::{AppHandleExt, StateFlags};
-
-use crate::app_state::AppState;
-use crate::emissions::Emission;
-
-mod app_state;
-mod config;
-mod components;
-mod emissions;
-mod ytdlp;
-
-pub fn emit_and_handle_result<R: Runtime, T: Serialize + Clone>(app_handle: &tauri::AppHandle<R>, emission: Emission, payload: T) {
-    match app_handle.emit(emission.as_string(), payload) {
-        Ok(_) => trace!("emitted event to frontend: {}", emission.as_string()),
-        Err(err) => error!("failed to emit event: {}, to frontend: {}", emission.as_string(), err),
-    }
-}
-
-#[derive(Parser, Debug, Clone)]
-#[command(version, about, long_about = None)]
-pub struct Args {
-    #[arg(long, short)]
-    log_level: String, 
-}
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run(args: Args) {
-    tauri::Builder::default()
-        .setup(|app| {
-            let window = app
-                .get_webview_window("main")
-                .expect("Failed to get main window");
-
-            let state = tauri::async_runtime::block_on(async {
-                AppState::init(app.app_handle().clone())
-                    .await
-                    .expect("Failed to initialize app state")
-            });
-
-            let state = Arc::new(Mutex::new(state));
-            app.manage(state.clone());
-
-            let app_handle = app.app_handle().clone();
-
-            window.on_window_event(move |event| {
-                match event {
-                    WindowEvent::CloseRequested { api, .. } => {
-                        // Prevent premature application close.
-                        api.prevent_close();
-
-                        let close_state: State<'_, Arc<Mutex<AppState>>> = app_handle.state();
-
-                        // Save in memory config to file.
-                        let config_dir = app_handle.path().app_config_dir();
-                        let config = tauri::async_runtime::block_on(async {
-                            close_state.lock().await.get_config()
-                        });
-
-                        match config_dir {
-                            Ok(config_dir) => match serde_json::to_string_pretty(&config) {
-                                Ok(config_as_str) => {
-                                    let config_file_location =
-                                        config_dir.join(config::CONFIG_FILENAME);
-                                    match fs::write(&config_file_location, &config_as_str) {
-                                        Ok(_) => {
-                                            log::debug!(
-                                                "saved {} to file.",
-                                                config::CONFIG_FILENAME
-                                            );
-                                        }
-                                        Err(err) => match err.kind() {
-                                            std::io::ErrorKind::NotFound => {
-                                                error!("config dir not found: {}", err);
-                                            }
-                                            _ => error!("generic: {}", err),
-                                        },
-                                    }
-                                }
-                                Err(err) => {
-                                    error!("error saving config to string: {}", err);
-                                }
-                            },
-                            Err(err) => {
-                                error!("retrieving config dir: {}", err);
-                            }
-                        }
-
-                        if let Err(err) = app_handle.save_window_state(StateFlags::all()) {
-                            error!("failed to save windows state: {}", err);
-                        }
-
-                        // Close application.
-                        std::process::exit(0);
-                    }
-                    _ => {}
-                }
-            });
-
-            Ok(())
-        })
-        .plugin(tauri_plugin_opener::init())
-        .plugin(
-            tauri_plugin_log::Builder::new()
-                .target(tauri_plugin_log::Target::new(
-                    tauri_plugin_log::TargetKind::LogDir {
-                        file_name: Some("logs".to_string()),
-                    },
-                ))
-                .level(str_to_log_level(&args.log_level))
-                .build(),
-        )
-        .plugin(tauri_plugin_window_state::Builder::default().build())
-        .invoke_handler(tauri::generate_handler![
-            // App State and Config Handlers
-            app_state::get_config,
-            app_state::update_skip_homepage,
-            // YT-DLP Handlers
-            components::install_ytdlp,
-            components::install_ffmpeg_ytdlp,
-            ytdlp::cancel_download,
-            ytdlp::download_from_options,
-            ytdlp::download_best_quality
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
-
-fn str_to_log_level(level: &str) -> LevelFilter {
-    match level {
-        "Trace" | "trace" => LevelFilter::Trace,
-        "Debug" | "debug" => LevelFilter::Debug,
-        "Info" | "info" => LevelFilter::Info,
-        "Warn" | "warn" => LevelFilter::Warn,
-        "Error" | "error" => LevelFilter::Error,
-        "Off" | "off" => LevelFilter::Off,
-        _ => panic!("unknown log level"),   
-    }
-}
\ No newline at end of file
+use serde::Serialize;
+use tauri::{Emitter, Runtime};
+use tauri_plugin_log::log::{error, trace};
+use tokio::sync::Mutex;
+use std::{
+    fs, sync::Arc, time::Duration,
+};
+use clap::Parser;
+use tauri::{Manager, State, WindowEvent};
+use tauri_plugin_log::log::{self, LevelFilter};
+use tauri_plugin_window_state::{AppHandleExt, StateFlags};
+
+use crate::app_state::AppState;
+use crate::emissions::Emission;
+use crate::engine::DownloadEngine;
+use crate::installs::InstallManager;
+use crate::queue::DownloadQueue;
+
+mod app_state;
+mod audio;
+mod auth;
+mod checksum;
+mod cli;
+mod config;
+mod components;
+mod container;
+mod dependencies;
+mod download_error;
+mod emissions;
+mod engine;
+mod error;
+mod events;
+#[cfg(all(test, unix))]
+mod fake_ytdlp;
+mod history;
+mod install_source;
+mod installs;
+mod mitigation;
+mod network;
+mod playlist;
+mod probe;
+mod progress;
+mod queue;
+mod retry;
+#[cfg(test)]
+mod testing;
+mod versions;
+mod ytdlp;
+
+/// How long closing the window waits for running downloads to stop.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn emit_and_handle_result<R: Runtime, T: Serialize + Clone>(app_handle: &tauri::AppHandle<R>, emission: Emission, payload: T) {
+    match app_handle.emit(emission.as_string(), payload) {
+        Ok(_) => trace!("emitted event to frontend: {}", emission.as_string()),
+        Err(err) => error!("failed to emit event: {}, to frontend: {}", emission.as_string(), err),
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    /// One of off, error, warn, info, debug or trace, case insensitive.
+    #[arg(long, short, global = true, default_value = "info", value_parser = str_to_log_level)]
+    log_level: LevelFilter,
+
+    /// Runs headless instead of opening the window.
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run(args: Args) {
+    if let Some(command) = args.command {
+        std::process::exit(cli::run(command, args.log_level));
+    }
+
+    tauri::Builder::default()
+        .setup(|app| {
+            let window = app
+                .get_webview_window("main")
+                .expect("Failed to get main window");
+
+            let config_dir = app.path().app_config_dir()?;
+            let state = AppState::init(
+                &config_dir,
+                &app.path().app_data_dir()?,
+                app.path().download_dir()?,
+                DownloadQueue::load(&config_dir),
+            )?;
+
+            let installs = InstallManager::new(events::tauri_sink(app.handle()));
+            let config = state.get_config();
+            if config.get_ytdlp_updates().auto_update() {
+                versions::spawn_ytdlp_update(&installs, config)?;
+            }
+            app.manage(installs);
+
+            let state = Arc::new(Mutex::new(state));
+            app.manage(state.clone());
+            let engine = DownloadEngine::new(state.clone(), events::tauri_sink(app.handle()));
+            app.manage(engine.clone());
+            // Jobs still queued when the app last closed start right away.
+            engine.schedule();
+
+            let app_handle = app.app_handle().clone();
+
+            window.on_window_event(move |event| {
+                if let WindowEvent::CloseRequested { api, .. } = event {
+                    // Prevent premature application close.
+                    api.prevent_close();
+
+                    let close_state: State<'_, Arc<Mutex<AppState>>> = app_handle.state();
+                    let engine: State<'_, DownloadEngine> = app_handle.state();
+
+                    // Save in memory config to file.
+                    let config_dir = app_handle.path().app_config_dir();
+                    let (config, queue_result) = tauri::async_runtime::block_on(async {
+                        // Stop running children so their jobs continue on next launch.
+                        engine.shutdown(SHUTDOWN_TIMEOUT).await;
+                        let close_state = close_state.lock().await;
+                        let queue_result = config_dir
+                            .as_ref()
+                            .map(|config_dir| close_state.queue().save(config_dir));
+                        (close_state.get_config(), queue_result)
+                    });
+
+                    // Save unfinished downloads so they are queued again on next launch.
+                    match queue_result {
+                        Ok(Ok(_)) => log::debug!("saved {} to file.", queue::QUEUE_FILENAME),
+                        Ok(Err(err)) => error!("saving {}: {}", queue::QUEUE_FILENAME, err),
+                        Err(_) => {}
+                    }
+
+                    match config_dir {
+                        Ok(config_dir) => match serde_json::to_string_pretty(&config) {
+                            Ok(config_as_str) => {
+                                let config_file_location =
+                                    config_dir.join(config::CONFIG_FILENAME);
+                                match fs::write(&config_file_location, &config_as_str) {
+                                    Ok(_) => {
+                                        log::debug!(
+                                            "saved {} to file.",
+                                            config::CONFIG_FILENAME
+                                        );
+                                    }
+                                    Err(err) => match err.kind() {
+                                        std::io::ErrorKind::NotFound => {
+                                            error!("config dir not found: {}", err);
+                                        }
+                                        _ => error!("generic: {}", err),
+                                    },
+                                }
+                            }
+                            Err(err) => {
+                                error!("error saving config to string: {}", err);
+                            }
+                        },
+                        Err(err) => {
+                            error!("retrieving config dir: {}", err);
+                        }
+                    }
+
+                    if let Err(err) = app_handle.save_window_state(StateFlags::all()) {
+                        error!("failed to save windows state: {}", err);
+                    }
+
+                    // Close application.
+                    std::process::exit(0);
+                }
+            });
+
+            Ok(())
+        })
+        .plugin(tauri_plugin_opener::init())
+        .plugin(
+            tauri_plugin_log::Builder::new()
+                .target(tauri_plugin_log::Target::new(
+                    tauri_plugin_log::TargetKind::LogDir {
+                        file_name: Some("logs".to_string()),
+                    },
+                ))
+                .level(args.log_level)
+                .build(),
+        )
+        .plugin(tauri_plugin_window_state::Builder::default().build())
+        .invoke_handler(tauri::generate_handler![
+            // App State and Config Handlers
+            app_state::get_config,
+            app_state::update_skip_homepage,
+            app_state::update_max_parallel_downloads,
+            app_state::update_mitigation_settings,
+            app_state::update_retry_policy,
+            network::update_network_settings,
+            // Download Queue Handlers
+            queue::enqueue_download,
+            queue::dequeue_download,
+            queue::move_download,
+            queue::list_downloads,
+            // Download History Handlers
+            history::list_history,
+            history::search_history,
+            history::delete_history_entry,
+            history::rerun_history_entry,
+            // Authentication Profile Handlers
+            auth::list_auth_profiles,
+            auth::set_auth_profile,
+            auth::remove_auth_profile,
+            // YT-DLP Handlers
+            components::install_ytdlp,
+            components::install_ffmpeg_ytdlp,
+            versions::check_ytdlp_update,
+            versions::upgrade_ytdlp,
+            versions::update_ytdlp_settings,
+            dependencies::check_dependencies,
+            dependencies::update_use_system_binaries,
+            install_source::update_install_sources,
+            installs::cancel_install,
+            installs::list_installs,
+            probe::probe_url,
+            playlist::probe_playlist,
+            playlist::enqueue_playlist,
+            ytdlp::cancel_download,
+            ytdlp::pause_download,
+            ytdlp::resume_download,
+            ytdlp::download_from_options,
+            ytdlp::download_best_quality
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
+
+fn str_to_log_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("unknown log level: {}, expected one of off, error, warn, info, debug or trace", level))
+}
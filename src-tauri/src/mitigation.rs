@@ -0,0 +1,324 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::download_error::DownloadError;
+use crate::error::VscraperError;
+use crate::ytdlp::{self, DownloadOptions};
+
+/// Longest sleep a profile may ask for, a day, anything longer is a typo.
+const MAX_SLEEP_SECONDS: f64 = 86_400.0;
+
+/// yt-dlp flags that make downloads look less like a bot, trading speed for fewer
+/// 429s and bot challenges.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct MitigationProfile {
+    name: String,
+    /// Seconds between the requests made while extracting, `--sleep-requests`.
+    #[serde(default)]
+    sleep_requests: Option<f64>,
+    /// Seconds before each download, a random value up to `max_sleep_interval` when set.
+    #[serde(default)]
+    min_sleep_interval: Option<f64>,
+    #[serde(default)]
+    max_sleep_interval: Option<f64>,
+    /// Seconds to wait before starting each entry of a playlist.
+    #[serde(default)]
+    playlist_sleep: Option<f64>,
+    /// Maximum download rate such as `50K` or `4.2M`, `--limit-rate`.
+    #[serde(default)]
+    limit_rate: Option<String>,
+    #[serde(default)]
+    user_agent: Option<String>,
+    /// Extra HTTP headers as `Name: value`.
+    #[serde(default)]
+    headers: Vec<String>,
+    #[serde(default)]
+    retries: Option<u32>,
+    #[serde(default)]
+    fragment_retries: Option<u32>,
+    /// Backoff between retries, such as `exp=1:30`, see `--retry-sleep`.
+    #[serde(default)]
+    retry_sleep: Option<String>,
+    /// Passed as `--extractor-args`, such as `youtube:player_client=web`.
+    #[serde(default)]
+    extractor_args: Vec<String>,
+    /// Stricter profile a job switches to when it is rate limited or challenged.
+    #[serde(default)]
+    escalate_to: Option<String>,
+}
+
+impl MitigationProfile {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// No sleep when `playlist_sleep` is out of range, a hand edited `settings.json` is
+    /// never validated.
+    pub fn playlist_sleep(&self) -> Duration {
+        Duration::try_from_secs_f64(self.playlist_sleep.unwrap_or_default()).unwrap_or_default()
+    }
+
+    /// Fails when a sleep is negative, not a number or longer than [`MAX_SLEEP_SECONDS`].
+    fn validate(&self) -> Result<(), VscraperError> {
+        let sleeps = [
+            ("sleep_requests", self.sleep_requests),
+            ("min_sleep_interval", self.min_sleep_interval),
+            ("max_sleep_interval", self.max_sleep_interval),
+            ("playlist_sleep", self.playlist_sleep),
+        ];
+        for (field, seconds) in sleeps {
+            if let Some(seconds) = seconds.filter(|seconds| !(0.0..=MAX_SLEEP_SECONDS).contains(seconds)) {
+                return Err(VscraperError::Mitigation(format!(
+                    "{} of profile {} is {}, expected 0 to {} seconds",
+                    field, self.name, seconds, MAX_SLEEP_SECONDS
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn ytdlp_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        let mut push = |flag: &str, value: String| {
+            args.push(String::from(flag));
+            args.push(value);
+        };
+        if let Some(seconds) = self.sleep_requests {
+            push("--sleep-requests", seconds.to_string());
+        }
+        if let Some(seconds) = self.min_sleep_interval {
+            push("--sleep-interval", seconds.to_string());
+            if let Some(max_seconds) = self.max_sleep_interval {
+                push("--max-sleep-interval", max_seconds.max(seconds).to_string());
+            }
+        }
+        if let Some(rate) = &self.limit_rate {
+            push("--limit-rate", rate.clone());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            push("--user-agent", user_agent.clone());
+        }
+        for header in &self.headers {
+            push("--add-headers", header.clone());
+        }
+        if let Some(retries) = self.retries {
+            push("--retries", retries.to_string());
+        }
+        if let Some(retries) = self.fragment_retries {
+            push("--fragment-retries", retries.to_string());
+        }
+        if let Some(retry_sleep) = &self.retry_sleep {
+            push("--retry-sleep", retry_sleep.clone());
+        }
+        for extractor_args in &self.extractor_args {
+            push("--extractor-args", extractor_args.clone());
+        }
+        args
+    }
+}
+
+/// The mitigation profiles and which one each job uses.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct MitigationSettings {
+    #[serde(default = "default_profiles")]
+    profiles: Vec<MitigationProfile>,
+    /// Profile of jobs that neither name one nor match a domain in `domains`.
+    #[serde(default = "default_profile_name")]
+    default_profile: String,
+    /// Profile name by domain, subdomains included.
+    #[serde(default)]
+    domains: HashMap<String, String>,
+}
+
+impl Default for MitigationSettings {
+    fn default() -> Self {
+        MitigationSettings {
+            profiles: default_profiles(),
+            default_profile: default_profile_name(),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+fn default_profile_name() -> String {
+    String::from("default")
+}
+
+/// `default` adds nothing and escalates to `gentle`, then `strict`.
+fn default_profiles() -> Vec<MitigationProfile> {
+    vec![
+        MitigationProfile {
+            name: default_profile_name(),
+            escalate_to: Some(String::from("gentle")),
+            ..MitigationProfile::default()
+        },
+        MitigationProfile {
+            name: String::from("gentle"),
+            sleep_requests: Some(1.0),
+            min_sleep_interval: Some(2.0),
+            max_sleep_interval: Some(6.0),
+            playlist_sleep: Some(3.0),
+            retries: Some(10),
+            fragment_retries: Some(10),
+            retry_sleep: Some(String::from("exp=1:30")),
+            escalate_to: Some(String::from("strict")),
+            ..MitigationProfile::default()
+        },
+        MitigationProfile {
+            name: String::from("strict"),
+            sleep_requests: Some(3.0),
+            min_sleep_interval: Some(10.0),
+            max_sleep_interval: Some(30.0),
+            playlist_sleep: Some(15.0),
+            limit_rate: Some(String::from("2M")),
+            retries: Some(20),
+            fragment_retries: Some(20),
+            retry_sleep: Some(String::from("exp=2:120")),
+            ..MitigationProfile::default()
+        },
+    ]
+}
+
+impl MitigationSettings {
+    fn profile(&self, name: &str) -> Option<&MitigationProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    /// Fails when a profile sleeps out of range, or following `escalate_to` from a profile
+    /// leads back to a profile on the way.
+    pub fn validate(&self) -> Result<(), VscraperError> {
+        for profile in &self.profiles {
+            profile.validate()?;
+            let mut chain = vec![profile.name.as_str()];
+            let mut current = profile;
+            while let Some(next) = current.escalate_to.as_deref().and_then(|name| self.profile(name)) {
+                let cycles = chain.contains(&next.name.as_str());
+                chain.push(&next.name);
+                if cycles {
+                    let chain = chain.join(" -> ");
+                    return Err(VscraperError::Mitigation(format!("profiles escalate in a cycle: {}", chain)));
+                }
+                current = next;
+            }
+        }
+        Ok(())
+    }
+
+    /// The profile named in `options`, else the one of the most specific matching domain,
+    /// else the default. Unknown names fall through to the next candidate.
+    pub fn profile_for(&self, options: &DownloadOptions) -> MitigationProfile {
+        self.select(options.url(), options.mitigation())
+    }
+
+    /// The profile of the most specific domain matching `url`, else the default.
+    pub fn profile_for_url(&self, url: &str) -> MitigationProfile {
+        self.select(url, None)
+    }
+
+    fn select(&self, url: &str, name: Option<&str>) -> MitigationProfile {
+        let host = ytdlp::url_host(url);
+        let domain_profile = self
+            .domains
+            .iter()
+            .filter(|(site, _)| host.as_deref().is_some_and(|host| ytdlp::host_matches(host, site)))
+            .max_by_key(|(site, _)| site.len())
+            .map(|(_, name)| name.as_str());
+
+        [name, domain_profile, Some(self.default_profile.as_str())]
+            .into_iter()
+            .flatten()
+            .find_map(|name| self.profile(name))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The stricter profile to retry with after `error`, if it is a rate limit or bot check.
+    /// Profiles named in `tried` are not escalated to again, so a job stops escalating even
+    /// when a hand-edited config loops.
+    pub fn escalate(
+        &self,
+        current: &MitigationProfile,
+        error: DownloadError,
+        tried: &[String],
+    ) -> Option<MitigationProfile> {
+        match error {
+            DownloadError::RateLimited | DownloadError::BotChallenge => self
+                .profile(current.escalate_to.as_deref()?)
+                .filter(|profile| !tried.contains(&profile.name))
+                .cloned(),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_mitigation_profile_selection_and_escalation() {
+    let settings: MitigationSettings = serde_json::from_value(serde_json::json!({
+        "domains": { "youtube.com": "gentle" }
+    }))
+    .unwrap();
+    let options = DownloadOptions::for_url;
+
+    let profile = settings.profile_for(&options("https://www.youtube.com/watch?v=1"));
+    assert_eq!(profile.name(), "gentle");
+    assert!(profile.ytdlp_args().windows(2).any(|args| args == ["--max-sleep-interval", "6"]));
+    assert_eq!(settings.profile_for(&options("https://vimeo.com/1")).name(), "default");
+    assert!(settings.profile_for(&options("https://vimeo.com/1")).ytdlp_args().is_empty());
+    let named = options("https://vimeo.com/1").with_mitigation(Some(String::from("strict")));
+    assert_eq!(settings.profile_for(&named).name(), "strict");
+    assert_eq!(settings.profile_for_url("https://m.youtube.com/watch?v=1").name(), "gentle");
+
+    let strict = settings.escalate(&profile, DownloadError::RateLimited, &[]).unwrap();
+    assert_eq!(strict.name(), "strict");
+    assert!(settings.escalate(&strict, DownloadError::BotChallenge, &[]).is_none());
+    assert!(settings.escalate(&profile, DownloadError::Network, &[]).is_none());
+    assert!(settings.escalate(&profile, DownloadError::RateLimited, &[String::from("strict")]).is_none());
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn test_mitigation_escalation_cycles_are_rejected() {
+    let settings: MitigationSettings = serde_json::from_value(serde_json::json!({
+        "profiles": [
+            { "name": "default", "escalate_to": "a" },
+            { "name": "a", "escalate_to": "b" },
+            { "name": "b", "escalate_to": "a" },
+        ]
+    }))
+    .unwrap();
+    let err = settings.validate().unwrap_err();
+    assert_eq!(err.to_string(), "mitigation: profiles escalate in a cycle: default -> a -> b -> a");
+
+    let looping: MitigationSettings = serde_json::from_value(serde_json::json!({
+        "profiles": [{ "name": "default", "escalate_to": "default" }]
+    }))
+    .unwrap();
+    assert!(looping.validate().is_err());
+    let default = looping.profile_for_url("https://example.com");
+    let tried = [String::from("default")];
+    assert!(looping.escalate(&default, DownloadError::RateLimited, &tried).is_none());
+}
+
+#[test]
+fn test_mitigation_sleeps_out_of_range() {
+    let settings: MitigationSettings = serde_json::from_value(serde_json::json!({
+        "profiles": [{ "name": "default", "playlist_sleep": 1e9 }]
+    }))
+    .unwrap();
+    let err = settings.validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "mitigation: playlist_sleep of profile default is 1000000000, expected 0 to 86400 seconds"
+    );
+
+    // Settings loaded from disk are not validated, such sleeps are skipped instead of panicking.
+    for seconds in [-1.0, f64::NAN, f64::INFINITY, 1e300] {
+        let profile = MitigationProfile {
+            playlist_sleep: Some(seconds),
+            ..MitigationProfile::default()
+        };
+        assert!(profile.validate().is_err());
+        assert!(profile.playlist_sleep().is_zero());
+    }
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::error::VscraperError;
+use crate::ytdlp;
+
+const PROXY_SCHEMES: [&str; 4] = ["http", "https", "socks5", "socks5h"];
+
+/// GitHub rejects API requests without a user agent.
+const HTTP_USER_AGENT: &str = concat!("vscraper/", env!("CARGO_PKG_VERSION"));
+
+const HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Route {
+    Proxy,
+    Direct,
+}
+
+/// How yt-dlp and the installers reach the network.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct NetworkSettings {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` URL, credentials included.
+    #[serde(default)]
+    proxy: Option<String>,
+    /// Route by domain, subdomains included. Hosts without a rule use the proxy when one is set.
+    #[serde(default)]
+    routes: HashMap<String, Route>,
+    /// Local address outgoing connections are bound to.
+    #[serde(default)]
+    source_address: Option<IpAddr>,
+}
+
+impl NetworkSettings {
+    pub fn validate(&self) -> Result<(), VscraperError> {
+        let Some(proxy) = &self.proxy else {
+            return Ok(());
+        };
+        match proxy.split_once("://") {
+            Some((scheme, rest)) if PROXY_SCHEMES.contains(&scheme.to_lowercase().as_str()) && !rest.is_empty() => {
+                Ok(())
+            }
+            _ => Err(VscraperError::Network(format!(
+                "unsupported proxy: {}, expected an http, https or socks5 url",
+                proxy
+            ))),
+        }
+    }
+
+    /// The proxy requests to `url` go through, following the most specific matching route.
+    pub fn proxy_for(&self, url: &str) -> Option<&str> {
+        let host = ytdlp::url_host(url);
+        let route = self
+            .routes
+            .iter()
+            .filter(|(site, _)| host.as_deref().is_some_and(|host| ytdlp::host_matches(host, site)))
+            .max_by_key(|(site, _)| site.len())
+            .map_or(Route::Proxy, |(_, route)| *route);
+        match route {
+            Route::Proxy => self.proxy.as_deref(),
+            Route::Direct => None,
+        }
+    }
+
+    /// yt-dlp arguments for requests to `url`. Direct routes pass an empty proxy so
+    /// yt-dlp ignores any proxy set in the environment too.
+    pub fn ytdlp_args(&self, url: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.proxy.is_some() {
+            args.push(String::from("--proxy"));
+            args.push(self.proxy_for(url).unwrap_or_default().to_string());
+        }
+        if let Some(address) = self.source_address {
+            args.push(String::from("--source-address"));
+            args.push(address.to_string());
+        }
+        args
+    }
+
+    /// HTTP client for requests to `url`, routed and bound like yt-dlp.
+    pub fn http_client(&self, url: &str) -> Result<reqwest::Client, VscraperError> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(HTTP_USER_AGENT)
+            .connect_timeout(HTTP_CONNECT_TIMEOUT)
+            .local_address(self.source_address);
+        builder = match self.proxy_for(url) {
+            Some(proxy) => builder.proxy(
+                reqwest::Proxy::all(proxy).map_err(|err| VscraperError::Network(format!("proxy {}: {}", proxy, err)))?,
+            ),
+            None => builder.no_proxy(),
+        };
+        builder
+            .build()
+            .map_err(|err| VscraperError::Network(format!("building http client: {}", err)))
+    }
+}
+
+#[tauri::command]
+pub async fn update_network_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    network: NetworkSettings,
+) -> Result<(), VscraperError> {
+    network.validate()?;
+    state.lock().await.set_network(network);
+    Ok(())
+}
+
+#[test]
+fn test_network_routes() {
+    let network: NetworkSettings = serde_json::from_value(serde_json::json!({
+        "proxy": "socks5://127.0.0.1:1080",
+        "routes": { "example.com": "direct", "cdn.example.com": "proxy" },
+        "source_address": "192.168.1.2",
+    }))
+    .unwrap();
+    assert!(network.validate().is_ok());
+
+    assert_eq!(
+        network.ytdlp_args("https://www.youtube.com/watch?v=1"),
+        ["--proxy", "socks5://127.0.0.1:1080", "--source-address", "192.168.1.2"]
+    );
+    assert_eq!(network.proxy_for("https://video.example.com/1"), None);
+    assert_eq!(network.ytdlp_args("https://example.com/1")[..2], ["--proxy", ""]);
+    assert_eq!(network.proxy_for("https://a.cdn.example.com/1"), Some("socks5://127.0.0.1:1080"));
+
+    assert!(NetworkSettings::default().ytdlp_args("https://example.com").is_empty());
+    let ftp: NetworkSettings = serde_json::from_value(serde_json::json!({ "proxy": "ftp://proxy" })).unwrap();
+    assert!(ftp.validate().is_err());
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_log::log::debug;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::auth::JobAuth;
+use crate::config::Config;
+use crate::engine::DownloadEngine;
+use crate::error::VscraperError;
+use crate::mitigation::MitigationProfile;
+use crate::ytdlp::DownloadOptions;
+
+/// A playlist or channel enumerated with `--flat-playlist`, without fetching each entry.
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaylistInfo {
+    id: String,
+    title: Option<String>,
+    uploader: Option<String>,
+    entries: Vec<PlaylistEntry>,
+}
+
+impl PlaylistInfo {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn entries(&self) -> &[PlaylistEntry] {
+        &self.entries
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlaylistEntry {
+    /// 1-based position, the same numbering as yt-dlp's `--playlist-items`.
+    index: usize,
+    id: Option<String>,
+    url: String,
+    title: Option<String>,
+    /// Seconds.
+    duration: Option<f64>,
+}
+
+impl PlaylistEntry {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+/// Which entries of a playlist to queue, everything when left empty.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PlaylistSelection {
+    /// First entry of an inclusive range.
+    start: Option<usize>,
+    /// Last entry of an inclusive range.
+    end: Option<usize>,
+    /// Explicit entries, takes precedence over the range.
+    #[serde(default)]
+    indices: Vec<usize>,
+}
+
+impl PlaylistSelection {
+    pub fn contains(&self, index: usize) -> bool {
+        if !self.indices.is_empty() {
+            return self.indices.contains(&index);
+        }
+        self.start.is_none_or(|start| index >= start) && self.end.is_none_or(|end| index <= end)
+    }
+}
+
+#[derive(Deserialize)]
+struct FlatPlaylist {
+    #[serde(rename = "_type")]
+    kind: Option<String>,
+    id: Option<String>,
+    title: Option<String>,
+    uploader: Option<String>,
+    #[serde(default)]
+    entries: Vec<FlatEntry>,
+}
+
+#[derive(Deserialize)]
+struct FlatEntry {
+    id: Option<String>,
+    url: Option<String>,
+    webpage_url: Option<String>,
+    title: Option<String>,
+    duration: Option<f64>,
+    /// Channels list their tabs as nested playlists.
+    #[serde(default)]
+    entries: Vec<FlatEntry>,
+}
+
+impl FlatPlaylist {
+    fn into_playlist(self) -> Option<PlaylistInfo> {
+        if self.kind.as_deref() != Some("playlist") {
+            return None;
+        }
+
+        let mut entries = Vec::new();
+        flatten_entries(self.entries, &mut entries);
+        Some(PlaylistInfo {
+            id: self.id.unwrap_or_default(),
+            title: self.title,
+            uploader: self.uploader,
+            entries,
+        })
+    }
+}
+
+fn flatten_entries(flat_entries: Vec<FlatEntry>, entries: &mut Vec<PlaylistEntry>) {
+    for entry in flat_entries {
+        if !entry.entries.is_empty() {
+            flatten_entries(entry.entries, entries);
+            continue;
+        }
+
+        if let Some(url) = entry.url.or(entry.webpage_url) {
+            entries.push(PlaylistEntry {
+                index: entries.len() + 1,
+                id: entry.id,
+                url,
+                title: entry.title,
+                duration: entry.duration,
+            });
+        }
+    }
+}
+
+/// Lists the entries of `url`, returns `None` when it is a single video.
+pub async fn enumerate(
+    config: &Config,
+    url: &str,
+    auth: &JobAuth,
+    mitigation: &MitigationProfile,
+) -> Result<Option<PlaylistInfo>, VscraperError> {
+    debug!("enumerating playlist: {}", url);
+    let output = Command::new(config.get_ytdlp_path())
+        .args(auth.args())
+        .args(mitigation.ytdlp_args())
+        .args(config.get_network().ytdlp_args(url))
+        .arg("--ffmpeg-location")
+        .arg(config.get_ffmpeg_path())
+        .arg("--flat-playlist")
+        .arg("--yes-playlist")
+        .arg("--dump-single-json")
+        .arg("--no-warnings")
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or("yt-dlp exited without output");
+        return Err(VscraperError::Ytdlp {
+            url: String::from(url),
+            reason: String::from(reason),
+        });
+    }
+
+    let playlist: FlatPlaylist = serde_json::from_slice(&output.stdout)?;
+    Ok(playlist.into_playlist())
+}
+
+#[tauri::command]
+pub async fn probe_playlist(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    url: String,
+) -> Result<Option<PlaylistInfo>, VscraperError> {
+    let (config, auth) = {
+        let state = state.lock().await;
+        (state.get_config(), state.auth().probe_auth(&url)?)
+    };
+    enumerate(&config, &url, &auth, &config.get_mitigation().profile_for_url(&url)).await
+}
+
+/// Queues one job per selected entry of the playlist at `options.url`, sharing the
+/// remaining options. Returns the playlist id the jobs are grouped under.
+#[tauri::command]
+pub async fn enqueue_playlist(
+    engine: State<'_, DownloadEngine>,
+    options: DownloadOptions,
+    selection: Option<PlaylistSelection>,
+) -> Result<Option<String>, VscraperError> {
+    engine.enqueue_playlist(options, selection).await
+}
+
+#[test]
+fn test_flat_playlist_selection() {
+    let playlist: FlatPlaylist = serde_json::from_str(
+        r#"{
+            "_type": "playlist", "id": "channel", "title": "Channel",
+            "entries": [
+                { "_type": "playlist", "id": "videos", "entries": [
+                    { "_type": "url", "id": "a", "url": "https://example.com/a", "title": "A" },
+                    { "_type": "url", "id": "b", "url": "https://example.com/b", "title": "B" }
+                ]},
+                { "_type": "url", "id": "c", "url": "https://example.com/c", "duration": 61.0 },
+                { "_type": "url", "id": "private" }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let playlist = playlist.into_playlist().unwrap();
+    let urls: Vec<&str> = playlist.entries.iter().map(|entry| entry.url.as_str()).collect();
+    assert_eq!(urls, vec!["https://example.com/a", "https://example.com/b", "https://example.com/c"]);
+    assert_eq!(playlist.entries[2].index, 3);
+
+    let range = PlaylistSelection { start: Some(2), end: None, indices: Vec::new() };
+    assert!(!range.contains(1) && range.contains(2) && range.contains(3));
+    let picked = PlaylistSelection { start: Some(2), end: None, indices: vec![1] };
+    assert!(picked.contains(1) && !picked.contains(2));
+
+    let video: FlatPlaylist = serde_json::from_str(r#"{ "_type": "video", "id": "a" }"#).unwrap();
+    assert!(video.into_playlist().is_none());
+}
+
+#[test]
+fn test_playlist_options_are_validated_before_listing() {
+    use crate::audio::{AudioCodec, AudioOptions};
+    use crate::container::Container;
+    use crate::events::LoggingSink;
+    use crate::queue::DownloadQueue;
+
+    let dir = crate::testing::TempDir::new("playlist");
+    let settings = r#"{ "ytdlp_path": "/nonexistent/yt-dlp", "history_path": ":memory:" }"#;
+    std::fs::write(dir.join(crate::config::CONFIG_FILENAME), settings).unwrap();
+    let state = AppState::init(&dir, &dir, dir.to_path_buf(), DownloadQueue::default()).unwrap();
+    let engine = DownloadEngine::new(Arc::new(Mutex::new(state)), Arc::new(LoggingSink));
+
+    let audio = AudioOptions::new(AudioCodec::Mp3, String::from("11"), false, false);
+    let options = DownloadOptions::new(
+        String::from("https://www.youtube.com/playlist?list=fake"),
+        crate::ytdlp::default_quality(),
+        Container::default(),
+        crate::ytdlp::default_name_format(),
+        Some(audio),
+    );
+    // Listing the playlist would fail to spawn the missing yt-dlp instead.
+    let enqueued = tauri::async_runtime::block_on(engine.enqueue_playlist(options, None));
+    assert!(matches!(enqueued, Err(VscraperError::InvalidAudioOptions(_))));
+    assert!(tauri::async_runtime::block_on(engine.state().lock()).queue().jobs().is_empty());
+}
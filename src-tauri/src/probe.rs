@@ -0,0 +1,135 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_log::log::debug;
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+use crate::app_state::AppState;
+use crate::auth::JobAuth;
+use crate::config::Config;
+use crate::error::VscraperError;
+use crate::mitigation::MitigationProfile;
+
+/// Metadata of a single video as reported by `yt-dlp --dump-single-json`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MediaInfo {
+    pub id: String,
+    pub title: String,
+    pub uploader: Option<String>,
+    /// Seconds.
+    pub duration: Option<f64>,
+    pub thumbnail: Option<String>,
+    pub extractor: String,
+    pub webpage_url: Option<String>,
+    #[serde(default)]
+    pub formats: Vec<MediaFormat>,
+}
+
+/// One of the formats a video is offered in, its `format_id` can be used as
+/// `DownloadOptions::quality`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MediaFormat {
+    pub format_id: String,
+    pub ext: String,
+    pub format_note: Option<String>,
+    pub resolution: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<f64>,
+    /// `None` for audio only formats.
+    #[serde(default, deserialize_with = "deserialize_codec")]
+    pub vcodec: Option<String>,
+    /// `None` for video only formats.
+    #[serde(default, deserialize_with = "deserialize_codec")]
+    pub acodec: Option<String>,
+    /// Total bitrate in KBit/s.
+    pub tbr: Option<f64>,
+    pub vbr: Option<f64>,
+    pub abr: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub filesize: Option<u64>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub filesize_approx: Option<u64>,
+}
+
+/// yt-dlp reports a missing stream as the codec `"none"`.
+fn deserialize_codec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let codec: Option<String> = Option::deserialize(deserializer)?;
+    Ok(codec.filter(|codec| codec != "none"))
+}
+
+/// Some extractors estimate sizes as floats.
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    let size: Option<f64> = Option::deserialize(deserializer)?;
+    Ok(size.map(|size| size as u64))
+}
+
+/// Fetches the metadata and format list of `url` without downloading it, authenticated
+/// and throttled the way a download of it would be.
+pub async fn probe(
+    config: &Config,
+    url: &str,
+    auth: &JobAuth,
+    mitigation: &MitigationProfile,
+) -> Result<MediaInfo, VscraperError> {
+    debug!("probing url: {}", url);
+    let output = Command::new(config.get_ytdlp_path())
+        .args(auth.args())
+        .args(mitigation.ytdlp_args())
+        .args(config.get_network().ytdlp_args(url))
+        .arg("--ffmpeg-location")
+        .arg(config.get_ffmpeg_path())
+        .arg("--dump-single-json")
+        .arg("--no-playlist")
+        .arg("--no-warnings")
+        .arg(url)
+        .stdin(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or("yt-dlp exited without output");
+        return Err(VscraperError::Ytdlp {
+            url: String::from(url),
+            reason: String::from(reason),
+        });
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[tauri::command]
+pub async fn probe_url(state: State<'_, Arc<Mutex<AppState>>>, url: String) -> Result<MediaInfo, VscraperError> {
+    let (config, auth) = {
+        let state = state.lock().await;
+        (state.get_config(), state.auth().probe_auth(&url)?)
+    };
+    probe(&config, &url, &auth, &config.get_mitigation().profile_for_url(&url)).await
+}
+
+#[test]
+fn test_media_info_from_json() {
+    let info: MediaInfo = serde_json::from_str(
+        r#"{
+            "id": "abc", "title": "Title", "uploader": null, "duration": 12.5,
+            "extractor": "youtube", "extractor_key": "Youtube", "webpage_url": "https://example.com",
+            "formats": [
+                { "format_id": "140", "ext": "m4a", "vcodec": "none", "acodec": "mp4a.40.2",
+                  "abr": 129.5, "filesize": 1234, "resolution": "audio only" },
+                { "format_id": "137", "ext": "mp4", "vcodec": "avc1.640028", "acodec": "none",
+                  "width": 1920, "height": 1080, "fps": 30, "filesize_approx": 9999.7 }
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    assert_eq!(info.extractor, "youtube");
+    assert_eq!(info.formats.len(), 2);
+    assert_eq!(info.formats[0].vcodec, None);
+    assert_eq!(info.formats[0].filesize, Some(1234));
+    assert_eq!(info.formats[1].acodec, None);
+    assert_eq!(info.formats[1].filesize_approx, Some(9999));
+}
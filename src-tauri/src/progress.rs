@@ -0,0 +1,309 @@
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+use ts_rs::TS;
+
+use crate::queue::JobId;
+
+/// Marks lines printed through `--progress-template`.
+const PROGRESS_PREFIX: &str = "vscraper-progress:";
+
+/// Fields yt-dlp substitutes into the download progress template, missing values become `null`.
+const DOWNLOAD_PROGRESS_FIELDS: &str = concat!(
+    r#"{"type":"download","status":"%(progress.status)s","#,
+    r#""downloaded_bytes":%(progress.downloaded_bytes|null)s,"#,
+    r#""total_bytes":%(progress.total_bytes|null)s,"#,
+    r#""total_bytes_estimate":%(progress.total_bytes_estimate|null)s,"#,
+    r#""speed":%(progress.speed|null)s,"#,
+    r#""eta":%(progress.eta|null)s,"#,
+    r#""fragment_index":%(progress.fragment_index|null)s,"#,
+    r#""fragment_count":%(progress.fragment_count|null)s}"#,
+);
+
+const POSTPROCESS_PROGRESS_FIELDS: &str = concat!(
+    r#"{"type":"postprocess","status":"%(progress.status)s","#,
+    r#""postprocessor":"%(progress.postprocessor)s"}"#,
+);
+
+/// Matches the human readable progress line of yt-dlp versions without `--progress-template`.
+const YTDLP_DOWNLOAD_UPDATE_REGEX: &str = concat!(
+    r"^\[download\]\s+",
+    r"(?:(?P<percent>\d+(?:\.\d+)?)%\s+of\s+~?\s*(?:(?P<total>\d+(?:\.\d+)?[KMGTPE]?i?B)|Unknown total size)",
+    r"|(?P<downloaded>\d+(?:\.\d+)?[KMGTPE]?i?B))",
+    r"(?:\s+in\s+\S+)?",
+    r"(?:\s+at\s+(?:(?P<speed>\d+(?:\.\d+)?[KMGTPE]?i?B)/s|Unknown\s+B/s|Unknown speed))?",
+    r"(?:\s+ETA\s+(?:(?P<eta>\d+(?::\d+)*)|Unknown))?",
+    r"(?:\s+\(frag\s+(?P<fragment_index>\d+)/(?P<fragment_count>\d+)\))?",
+);
+
+static PROGRESS_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(YTDLP_DOWNLOAD_UPDATE_REGEX).expect("progress regex is valid"));
+
+/// Arguments that make yt-dlp print machine readable progress for downloads and postprocessors.
+pub fn progress_template_args() -> [String; 4] {
+    [
+        String::from("--progress-template"),
+        format!("download:{}{}", PROGRESS_PREFIX, DOWNLOAD_PROGRESS_FIELDS),
+        String::from("--progress-template"),
+        format!("postprocess:{}{}", PROGRESS_PREFIX, POSTPROCESS_PROGRESS_FIELDS),
+    ]
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum DownloadPhase {
+    Downloading,
+    Merging,
+    /// ffmpeg transcoding to the codec requested in `AudioOptions`.
+    ExtractingAudio,
+    PostProcessing,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq, TS)]
+#[ts(export)]
+pub struct DownloadProgress {
+    job_id: JobId,
+    url: String,
+    phase: DownloadPhase,
+    percent: Option<f64>,
+    #[ts(type = "number | null")]
+    downloaded_bytes: Option<u64>,
+    #[ts(type = "number | null")]
+    total_bytes: Option<u64>,
+    /// Bytes per second.
+    speed: Option<f64>,
+    /// Seconds.
+    #[ts(type = "number | null")]
+    eta: Option<u64>,
+    #[ts(type = "number | null")]
+    fragment_index: Option<u64>,
+    #[ts(type = "number | null")]
+    fragment_count: Option<u64>,
+    /// Name of the running yt-dlp postprocessor, such as `Merger`.
+    postprocessor: Option<String>,
+    /// Starts at 1 and counts the retries of a failed download.
+    attempt: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TemplateProgress {
+    Download {
+        status: String,
+        downloaded_bytes: Option<f64>,
+        total_bytes: Option<f64>,
+        total_bytes_estimate: Option<f64>,
+        speed: Option<f64>,
+        eta: Option<f64>,
+        fragment_index: Option<f64>,
+        fragment_count: Option<f64>,
+    },
+    Postprocess {
+        status: String,
+        postprocessor: String,
+    },
+}
+
+impl DownloadProgress {
+    /// Parses a line of yt-dlp output, preferring the progress template and falling back
+    /// to the human readable progress line.
+    pub fn parse(job_id: &JobId, url: &str, line: &str) -> Option<DownloadProgress> {
+        match line.strip_prefix(PROGRESS_PREFIX) {
+            Some(json) => Self::from_template(job_id, url, json),
+            None => Self::from_progress_line(job_id, url, line),
+        }
+    }
+
+    fn from_template(job_id: &JobId, url: &str, json: &str) -> Option<DownloadProgress> {
+        let progress = match serde_json::from_str::<TemplateProgress>(json) {
+            Ok(progress) => progress,
+            Err(_) => return None,
+        };
+
+        match progress {
+            TemplateProgress::Download {
+                status,
+                downloaded_bytes,
+                total_bytes,
+                total_bytes_estimate,
+                speed,
+                eta,
+                fragment_index,
+                fragment_count,
+            } => {
+                let downloaded_bytes = downloaded_bytes.map(|bytes| bytes as u64);
+                let total_bytes = total_bytes.or(total_bytes_estimate).map(|bytes| bytes as u64);
+                let percent = match (status.as_str(), downloaded_bytes, total_bytes) {
+                    ("finished", _, _) => Some(100.0),
+                    (_, Some(downloaded), Some(total)) if total > 0 => {
+                        Some(downloaded as f64 / total as f64 * 100.0)
+                    }
+                    _ => None,
+                };
+                Some(DownloadProgress {
+                    job_id: job_id.clone(),
+                    url: url.to_string(),
+                    phase: DownloadPhase::Downloading,
+                    percent,
+                    downloaded_bytes,
+                    total_bytes,
+                    speed,
+                    eta: eta.map(|eta| eta as u64),
+                    fragment_index: fragment_index.map(|index| index as u64),
+                    fragment_count: fragment_count.map(|count| count as u64),
+                    postprocessor: None,
+                    attempt: 1,
+                })
+            }
+            TemplateProgress::Postprocess {
+                status,
+                postprocessor,
+            } => Some(DownloadProgress {
+                job_id: job_id.clone(),
+                url: url.to_string(),
+                phase: match postprocessor.as_str() {
+                    "Merger" => DownloadPhase::Merging,
+                    "ExtractAudio" => DownloadPhase::ExtractingAudio,
+                    _ => DownloadPhase::PostProcessing,
+                },
+                percent: match status.as_str() {
+                    "finished" => Some(100.0),
+                    _ => None,
+                },
+                downloaded_bytes: None,
+                total_bytes: None,
+                speed: None,
+                eta: None,
+                fragment_index: None,
+                fragment_count: None,
+                postprocessor: Some(postprocessor),
+                attempt: 1,
+            }),
+        }
+    }
+
+    fn from_progress_line(job_id: &JobId, url: &str, line: &str) -> Option<DownloadProgress> {
+        let captures = PROGRESS_REGEX.captures(line)?;
+        let number = |captures: &Captures, name: &str| -> Option<u64> {
+            captures.name(name).and_then(|value| value.as_str().parse().ok())
+        };
+
+        let percent: Option<f64> = captures
+            .name("percent")
+            .and_then(|percent| percent.as_str().parse().ok());
+        let total_bytes = captures.name("total").and_then(|total| parse_size(total.as_str()));
+        let downloaded_bytes = match captures.name("downloaded") {
+            Some(downloaded) => parse_size(downloaded.as_str()),
+            None => percent
+                .zip(total_bytes)
+                .map(|(percent, total)| (total as f64 * percent / 100.0) as u64),
+        };
+
+        Some(DownloadProgress {
+            job_id: job_id.clone(),
+            url: url.to_string(),
+            phase: DownloadPhase::Downloading,
+            percent,
+            downloaded_bytes,
+            total_bytes,
+            speed: captures
+                .name("speed")
+                .and_then(|speed| parse_size(speed.as_str()))
+                .map(|speed| speed as f64),
+            eta: captures.name("eta").and_then(|eta| parse_duration(eta.as_str())),
+            fragment_index: number(&captures, "fragment_index"),
+            fragment_count: number(&captures, "fragment_count"),
+            postprocessor: None,
+            attempt: 1,
+        })
+    }
+
+    pub fn with_attempt(self, attempt: u32) -> DownloadProgress {
+        DownloadProgress { attempt, ..self }
+    }
+}
+
+/// Converts sizes such as `10.5MiB`, `300KB` or `12B` to bytes.
+fn parse_size(size: &str) -> Option<u64> {
+    let unit_start = size.find(|c: char| c.is_ascii_alphabetic())?;
+    let (value, unit) = size.split_at(unit_start);
+    let value: f64 = value.parse().ok()?;
+
+    let base: f64 = match unit.contains('i') {
+        true => 1024.0,
+        false => 1000.0,
+    };
+    let exponent = match unit.chars().next()? {
+        'B' => 0,
+        'K' => 1,
+        'M' => 2,
+        'G' => 3,
+        'T' => 4,
+        'P' => 5,
+        'E' => 6,
+        _ => return None,
+    };
+    Some((value * base.powi(exponent)) as u64)
+}
+
+/// Converts `SS`, `MM:SS` or `HH:MM:SS` to seconds.
+fn parse_duration(duration: &str) -> Option<u64> {
+    duration
+        .split(':')
+        .try_fold(0, |seconds, part| Some(seconds * 60 + part.parse::<u64>().ok()?))
+}
+
+#[test]
+fn test_parse_progress_template() {
+    let job_id = JobId::new();
+    let line = concat!(
+        r#"vscraper-progress:{"type":"download","status":"downloading","downloaded_bytes":1024,"#,
+        r#""total_bytes":null,"total_bytes_estimate":4096.0,"speed":512.5,"eta":6,"#,
+        r#""fragment_index":3,"fragment_count":12}"#,
+    );
+    let progress = DownloadProgress::parse(&job_id, "url", line).unwrap();
+    assert_eq!(progress.phase, DownloadPhase::Downloading);
+    assert_eq!(progress.percent, Some(25.0));
+    assert_eq!(progress.total_bytes, Some(4096));
+    assert_eq!(progress.speed, Some(512.5));
+    assert_eq!(progress.eta, Some(6));
+    assert_eq!(progress.fragment_count, Some(12));
+
+    let line = r#"vscraper-progress:{"type":"postprocess","status":"started","postprocessor":"Merger"}"#;
+    let progress = DownloadProgress::parse(&job_id, "url", line).unwrap();
+    assert_eq!(progress.phase, DownloadPhase::Merging);
+}
+
+#[test]
+fn test_parse_progress_line_fallback() {
+    let job_id = JobId::new();
+    let progress = DownloadProgress::parse(
+        &job_id,
+        "url",
+        "[download]  45.0% of ~  10.00MiB at    2.00MiB/s ETA 00:03 (frag 9/20)",
+    )
+    .unwrap();
+    assert_eq!(progress.percent, Some(45.0));
+    assert_eq!(progress.total_bytes, Some(10 * 1024 * 1024));
+    assert_eq!(progress.speed, Some(2.0 * 1024.0 * 1024.0));
+    assert_eq!(progress.eta, Some(3));
+    assert_eq!(progress.fragment_index, Some(9));
+
+    let progress =
+        DownloadProgress::parse(&job_id, "url", "[download]    1.00MiB at  500.00B/s (00:00:02)").unwrap();
+    assert_eq!(progress.percent, None);
+    assert_eq!(progress.downloaded_bytes, Some(1024 * 1024));
+    assert_eq!(progress.speed, Some(500.0));
+
+    let progress = DownloadProgress::parse(
+        &job_id,
+        "url",
+        "[download]   3.1% of Unknown total size at Unknown B/s ETA Unknown",
+    )
+    .unwrap();
+    assert_eq!(progress.total_bytes, None);
+    assert_eq!(progress.eta, None);
+
+    assert!(DownloadProgress::parse(&job_id, "url", "[youtube] Extracting URL").is_none());
+}
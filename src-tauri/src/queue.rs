@@ -0,0 +1,504 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tauri::async_runtime::{channel, Receiver, Sender};
+use tauri::State;
+use tauri_plugin_log::log::error;
+use tokio::sync::Mutex;
+use ts_rs::TS;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::engine::DownloadEngine;
+use crate::error::VscraperError;
+use crate::ytdlp::DownloadOptions;
+
+pub const QUEUE_FILENAME: &str = "queue.json";
+
+/// Messages a job's control channel buffers, commands send them one at a time.
+const CONTROL_CHANNEL_CAPACITY: usize = 100;
+
+/// Identifies one job for its whole life, so the same URL can be queued more than once.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash, TS)]
+#[serde(transparent)]
+#[ts(export)]
+pub struct JobId(Uuid);
+
+impl JobId {
+    pub fn new() -> JobId {
+        JobId(Uuid::new_v4())
+    }
+}
+
+impl FromStr for JobId {
+    type Err = uuid::Error;
+
+    fn from_str(id: &str) -> Result<JobId, uuid::Error> {
+        Uuid::parse_str(id).map(JobId)
+    }
+}
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Finished,
+    Failed,
+    Cancelled,
+    /// Was running when the app closed or crashed, waits for the user to resume it.
+    Interrupted,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Paused => "paused",
+            JobState::Finished => "finished",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+            JobState::Interrupted => "interrupted",
+        }
+    }
+
+    /// Whether the job is still waiting or downloading.
+    pub fn is_active(&self) -> bool {
+        matches!(self, JobState::Queued | JobState::Running | JobState::Paused | JobState::Interrupted)
+    }
+
+    /// Whether the job holds one of the parallel download slots. A paused job keeps
+    /// its slot so resuming it never exceeds the limit.
+    fn is_started(&self) -> bool {
+        matches!(self, JobState::Running | JobState::Paused)
+    }
+}
+
+/// Messages sent from commands to the task running a job.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlMessage {
+    /// Stops the yt-dlp child, keeping the partial download.
+    Pause,
+    /// Restarts a paused job with `--continue`.
+    Resume,
+    Cancel,
+    /// Stops the yt-dlp child because the app is closing, the job continues on next launch.
+    Shutdown,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Job {
+    /// Queue files written before jobs had ids get fresh ones.
+    #[serde(default = "JobId::new")]
+    id: JobId,
+    options: DownloadOptions,
+    state: JobState,
+    /// Set when a previous run may have left a partial file to continue from.
+    #[serde(default)]
+    resume: bool,
+    /// Id of the playlist this job was expanded from.
+    #[serde(default)]
+    playlist_id: Option<String>,
+}
+
+impl Job {
+    pub fn id(&self) -> &JobId {
+        &self.id
+    }
+
+    pub fn url(&self) -> &str {
+        self.options.url()
+    }
+
+    pub fn options(&self) -> &DownloadOptions {
+        &self.options
+    }
+
+    pub fn state(&self) -> JobState {
+        self.state
+    }
+
+    pub fn is_resume(&self) -> bool {
+        self.resume
+    }
+
+    pub fn playlist_id(&self) -> Option<&str> {
+        self.playlist_id.as_deref()
+    }
+}
+
+/// Number of jobs of a playlist in each state.
+#[derive(Clone, Debug, Default, Serialize, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct PlaylistProgress {
+    playlist_id: String,
+    total: usize,
+    queued: usize,
+    running: usize,
+    paused: usize,
+    finished: usize,
+    failed: usize,
+    cancelled: usize,
+    interrupted: usize,
+}
+
+/// FIFO queue of download jobs, the order of `jobs` is the order they are started in.
+/// Jobs are dropped once they end, the history keeps them.
+#[derive(Default)]
+pub struct DownloadQueue {
+    jobs: VecDeque<Job>,
+    /// Control channels of the started jobs, dropped when they end.
+    controls: HashMap<JobId, Sender<ControlMessage>>,
+    /// Directory the queue was loaded from, every change is written back to it so a
+    /// crash loses no jobs.
+    dir: Option<PathBuf>,
+}
+
+impl DownloadQueue {
+    /// Restores the queue saved by [`DownloadQueue::save`]. Jobs that were running or
+    /// paused when the app closed are interrupted, they continue their partial files
+    /// once resumed.
+    pub fn load(dir: &Path) -> DownloadQueue {
+        let file = dir.join(QUEUE_FILENAME);
+        let jobs: Vec<Job> = match fs::read(&file) {
+            Ok(file_data) => match serde_json::from_slice(&file_data) {
+                Ok(jobs) => jobs,
+                Err(err) => {
+                    error!("parsing {}: {}", QUEUE_FILENAME, err);
+                    Vec::new()
+                }
+            },
+            Err(err) => match err.kind() {
+                std::io::ErrorKind::NotFound => Vec::new(),
+                _ => {
+                    error!("reading {}: {}", QUEUE_FILENAME, err);
+                    Vec::new()
+                }
+            },
+        };
+
+        DownloadQueue {
+            jobs: jobs
+                .into_iter()
+                .filter(|job| job.state.is_active())
+                .map(|job| match job.state.is_started() || job.state == JobState::Interrupted {
+                    true => Job {
+                        resume: true,
+                        state: JobState::Interrupted,
+                        ..job
+                    },
+                    false => job,
+                })
+                .collect(),
+            controls: HashMap::new(),
+            dir: Some(dir.to_path_buf()),
+        }
+    }
+
+    /// Writes every unfinished job to the queue file in `dir`, replacing it in one step
+    /// so a crash never leaves a truncated file behind.
+    pub fn save(&self, dir: &Path) -> std::io::Result<()> {
+        let jobs: Vec<&Job> = self.jobs.iter().filter(|job| job.state.is_active()).collect();
+        let jobs_as_str = serde_json::to_string_pretty(&jobs)?;
+        let temp_file = dir.join(format!("{}.tmp", QUEUE_FILENAME));
+        fs::write(&temp_file, jobs_as_str)?;
+        fs::rename(temp_file, dir.join(QUEUE_FILENAME))
+    }
+
+    /// Saves the queue to the directory it was loaded from, if any.
+    fn persist(&self) {
+        if let Some(dir) = &self.dir {
+            if let Err(err) = self.save(dir) {
+                error!("saving {}: {}", QUEUE_FILENAME, err);
+            }
+        }
+    }
+
+    pub fn jobs(&self) -> Vec<Job> {
+        self.jobs.iter().cloned().collect()
+    }
+
+    pub fn get(&self, id: &JobId) -> Option<&Job> {
+        self.jobs.iter().find(|job| job.id == *id)
+    }
+
+    /// Appends a job to the back of the queue and returns its id.
+    pub fn enqueue(&mut self, options: DownloadOptions, playlist_id: Option<String>) -> JobId {
+        let id = JobId::new();
+        self.jobs.push_back(Job {
+            id: id.clone(),
+            options,
+            state: JobState::Queued,
+            resume: false,
+            playlist_id,
+        });
+        self.persist();
+        id
+    }
+
+    /// Removes a job that is not currently running or paused.
+    pub fn remove(&mut self, id: &JobId) -> Option<Job> {
+        let index = self
+            .jobs
+            .iter()
+            .position(|job| job.id == *id && !job.state.is_started())?;
+        let job = self.jobs.remove(index);
+        self.persist();
+        job
+    }
+
+    /// Moves a job to `position`, positions past the end move it to the back.
+    pub fn move_to(&mut self, id: &JobId, position: usize) -> bool {
+        match self.jobs.iter().position(|job| job.id == *id) {
+            Some(index) => {
+                if let Some(job) = self.jobs.remove(index) {
+                    let position = position.min(self.jobs.len());
+                    self.jobs.insert(position, job);
+                }
+                self.persist();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks queued jobs as running, in order, until `max_parallel` jobs are started
+    /// and returns the jobs that should now be run. Each job's control channel is
+    /// registered right away, so it can be paused or cancelled before its run begins.
+    pub fn start_next(&mut self, max_parallel: usize) -> Vec<(Job, Receiver<ControlMessage>)> {
+        let mut running = self
+            .jobs
+            .iter()
+            .filter(|job| job.state.is_started())
+            .count();
+
+        let mut ready = Vec::new();
+        for job in self.jobs.iter_mut() {
+            if running >= max_parallel {
+                break;
+            }
+            if job.state == JobState::Queued {
+                job.state = JobState::Running;
+                let (tx, rx) = channel(CONTROL_CHANNEL_CAPACITY);
+                self.controls.insert(job.id.clone(), tx);
+                ready.push((job.clone(), rx));
+                running += 1;
+            }
+        }
+        if !ready.is_empty() {
+            self.persist();
+        }
+        ready
+    }
+
+    /// Cancels a job that has not been started yet, or was interrupted.
+    pub fn cancel_queued(&mut self, id: &JobId) -> bool {
+        match self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == *id && matches!(job.state, JobState::Queued | JobState::Interrupted))
+        {
+            Some(job) => {
+                job.state = JobState::Cancelled;
+                self.prune();
+                self.persist();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Queues an interrupted job again, it continues from its partial file.
+    pub fn requeue_interrupted(&mut self, id: &JobId) -> bool {
+        match self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == *id && job.state == JobState::Interrupted)
+        {
+            Some(job) => {
+                job.state = JobState::Queued;
+                self.persist();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn playlist_progress(&self, playlist_id: &str) -> PlaylistProgress {
+        let mut progress = PlaylistProgress {
+            playlist_id: playlist_id.to_string(),
+            ..PlaylistProgress::default()
+        };
+        for job in self.jobs.iter().filter(|job| job.playlist_id() == Some(playlist_id)) {
+            progress.total += 1;
+            match job.state {
+                JobState::Queued => progress.queued += 1,
+                JobState::Running => progress.running += 1,
+                JobState::Paused => progress.paused += 1,
+                JobState::Finished => progress.finished += 1,
+                JobState::Failed => progress.failed += 1,
+                JobState::Cancelled => progress.cancelled += 1,
+                JobState::Interrupted => progress.interrupted += 1,
+            }
+        }
+        progress
+    }
+
+    pub fn get_control(&self, id: &JobId) -> Option<&Sender<ControlMessage>> {
+        self.controls.get(id)
+    }
+
+    /// Control channels of every started job.
+    pub fn controls(&self) -> Vec<Sender<ControlMessage>> {
+        self.controls.values().cloned().collect()
+    }
+
+    /// Moves a started job between running and paused.
+    pub fn set_state(&mut self, id: &JobId, state: JobState) {
+        if let Some(job) = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == *id && job.state.is_started())
+        {
+            job.state = state;
+            job.resume = true;
+            self.persist();
+        }
+    }
+
+    /// Records the final state of a started job, drops its control channel and returns
+    /// the progress of its playlist, counting the job. [`JobState::Interrupted`] keeps
+    /// the job for the next launch.
+    pub fn finish(&mut self, id: &JobId, state: JobState) -> Option<PlaylistProgress> {
+        self.controls.remove(id);
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == *id && job.state.is_started())?;
+        job.state = state;
+        job.resume |= state == JobState::Interrupted;
+        let playlist_id = job.playlist_id.clone();
+
+        let progress = playlist_id.map(|playlist_id| self.playlist_progress(&playlist_id));
+        self.prune();
+        self.persist();
+        progress
+    }
+
+    /// Drops the jobs that ended. Ended entries of a playlist stay until all of its jobs
+    /// ended, so its progress still counts them.
+    fn prune(&mut self) {
+        let active_playlists: HashSet<String> = self
+            .jobs
+            .iter()
+            .filter(|job| job.state.is_active())
+            .filter_map(|job| job.playlist_id.clone())
+            .collect();
+        self.jobs.retain(|job| {
+            job.state.is_active() || job.playlist_id.as_ref().is_some_and(|id| active_playlists.contains(id))
+        });
+    }
+}
+
+#[tauri::command]
+pub async fn enqueue_download(
+    engine: State<'_, DownloadEngine>,
+    options: DownloadOptions,
+) -> Result<JobId, VscraperError> {
+    engine.enqueue(options).await
+}
+
+#[tauri::command]
+pub async fn dequeue_download(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    job_id: JobId,
+) -> Result<bool, VscraperError> {
+    Ok(state.lock().await.queue_mut().remove(&job_id).is_some())
+}
+
+#[tauri::command]
+pub async fn move_download(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    job_id: JobId,
+    position: usize,
+) -> Result<bool, VscraperError> {
+    Ok(state.lock().await.queue_mut().move_to(&job_id, position))
+}
+
+#[tauri::command]
+pub async fn list_downloads(state: State<'_, Arc<Mutex<AppState>>>) -> Result<Vec<Job>, VscraperError> {
+    Ok(state.lock().await.queue().jobs())
+}
+
+#[test]
+fn test_queue_respects_max_parallel() {
+    let options = DownloadOptions::for_url;
+
+    let mut queue = DownloadQueue::default();
+    let ids: Vec<JobId> = ["https://a.example", "https://b.example", "https://c.example"]
+        .into_iter()
+        .map(|url| queue.enqueue(options(url), Some(String::from("playlist"))))
+        .collect();
+    // The same URL can be queued again under its own id.
+    assert_ne!(queue.enqueue(options("https://a.example"), None), ids[0]);
+
+    assert!(queue.move_to(&ids[2], 0));
+    let started = queue.start_next(2);
+    let started: Vec<&str> = started.iter().map(|(job, _)| job.url()).collect();
+    assert_eq!(started, vec!["https://c.example", "https://a.example"]);
+    assert!(queue.start_next(2).is_empty());
+
+    // Started jobs can be controlled before their run begins.
+    assert!(queue.get_control(&ids[2]).is_some());
+    let progress = queue.finish(&ids[2], JobState::Finished).unwrap();
+    assert_eq!((progress.total, progress.running, progress.finished), (3, 1, 1));
+    assert!(queue.get_control(&ids[2]).is_none());
+    let started = queue.start_next(2);
+    assert_eq!(started.len(), 1);
+    assert_eq!(started[0].0.url(), "https://b.example");
+
+    let progress = queue.playlist_progress("playlist");
+    assert_eq!((progress.total, progress.running, progress.finished), (3, 2, 1));
+
+    // Ended jobs are dropped once their playlist has ended too.
+    queue.finish(&ids[0], JobState::Failed);
+    let progress = queue.finish(&ids[1], JobState::Cancelled).unwrap();
+    assert_eq!((progress.total, progress.finished, progress.failed, progress.cancelled), (3, 1, 1, 1));
+    assert_eq!(queue.jobs().len(), 1);
+}
+
+#[test]
+fn test_queue_interrupts_started_jobs_on_load() {
+    let dir = crate::testing::TempDir::new("queue");
+    let mut queue = DownloadQueue::load(&dir);
+    let options = DownloadOptions::for_url;
+    let running = queue.enqueue(options("https://a.example"), None);
+    let queued = queue.enqueue(options("https://b.example"), None);
+    queue.start_next(1);
+    // Dropped without saving, as if the app had crashed.
+    drop(queue);
+
+    let mut queue = DownloadQueue::load(&dir);
+    let running_job = queue.get(&running).unwrap();
+    assert_eq!(running_job.state(), JobState::Interrupted);
+    assert!(running_job.is_resume());
+    assert_eq!(queue.get(&queued).unwrap().state(), JobState::Queued);
+    assert_eq!(queue.start_next(2).len(), 1);
+
+    assert!(queue.requeue_interrupted(&running));
+    assert_eq!(DownloadQueue::load(&dir).get(&running).unwrap().state(), JobState::Queued);
+}
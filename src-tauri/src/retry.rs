@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::download_error::DownloadError;
+
+/// When and how often a failed job is run again.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RetryPolicy {
+    /// Runs in total, the first one included, so `1` disables retries.
+    #[serde(default = "default_max_attempts")]
+    max_attempts: u32,
+    /// Delay before the first retry, multiplied by `multiplier` for each one after it.
+    #[serde(default = "default_initial_delay_ms")]
+    initial_delay_ms: u64,
+    #[serde(default = "default_max_delay_ms")]
+    max_delay_ms: u64,
+    #[serde(default = "default_multiplier")]
+    multiplier: f64,
+    /// Failures worth retrying, others such as an unavailable video fail right away.
+    #[serde(default = "default_retryable")]
+    retryable: Vec<DownloadError>,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_initial_delay_ms() -> u64 {
+    2_000
+}
+
+fn default_max_delay_ms() -> u64 {
+    60_000
+}
+
+fn default_multiplier() -> f64 {
+    2.0
+}
+
+fn default_retryable() -> Vec<DownloadError> {
+    vec![DownloadError::Network, DownloadError::RateLimited]
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: default_max_attempts(),
+            initial_delay_ms: default_initial_delay_ms(),
+            max_delay_ms: default_max_delay_ms(),
+            multiplier: default_multiplier(),
+            retryable: default_retryable(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn with_max_attempts(self, max_attempts: u32) -> RetryPolicy {
+        RetryPolicy { max_attempts, ..self }
+    }
+
+    /// How long to wait before running a job again after its `attempt`th run failed
+    /// with `error`, `None` when it should fail instead.
+    pub fn delay(&self, attempt: u32, error: DownloadError) -> Option<Duration> {
+        if attempt >= self.max_attempts || !self.retryable.contains(&error) {
+            return None;
+        }
+        let delay = self.initial_delay_ms as f64 * self.multiplier.max(1.0).powi(attempt as i32 - 1);
+        Some(Duration::from_millis(delay.min(self.max_delay_ms as f64) as u64))
+    }
+}
+
+#[test]
+fn test_retry_backoff() {
+    let policy = RetryPolicy::default().with_max_attempts(5);
+    let delays: Vec<Option<Duration>> = (1..=5).map(|attempt| policy.delay(attempt, DownloadError::Network)).collect();
+    assert_eq!(
+        delays,
+        [2, 4, 8, 16].map(|secs| Some(Duration::from_secs(secs))).into_iter().chain([None]).collect::<Vec<_>>()
+    );
+    assert_eq!(policy.delay(1, DownloadError::Unavailable), None);
+
+    let capped: RetryPolicy = serde_json::from_str(r#"{ "max_attempts": 10, "max_delay_ms": 5000 }"#).unwrap();
+    assert_eq!(capped.delay(8, DownloadError::RateLimited), Some(Duration::from_secs(5)));
+}
@@ -0,0 +1,33 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Fresh directory under the system temp dir, removed with its contents when dropped,
+/// so a failing test leaves nothing behind for the next run.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates `vscraper-<prefix>-<uuid>`, unique across the tests of one binary.
+    pub fn new(prefix: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("vscraper-{}-{}", prefix, Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::State;
+use tauri_plugin_log::log::{error, info};
+use tokio::sync::Mutex;
+use ts_rs::TS;
+
+use crate::app_state::AppState;
+use crate::components::{self, Installed, YTDLP_EXECUTABLE, YTDLP_GITHUB};
+use crate::config::Config;
+use crate::emissions::{Emission, InstallPhase};
+use crate::error::VscraperError;
+use crate::history;
+use crate::installs::{InstallManager, InstallTask};
+use crate::network::NetworkSettings;
+
+/// Records the installed release of each managed binary, next to the binaries.
+pub const VERSIONS_FILENAME: &str = "versions.json";
+
+const GITHUB_API: &str = "https://api.github.com";
+
+/// Which yt-dlp builds to follow, extractor fixes land in nightly and master days
+/// before a stable release.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum ReleaseChannel {
+    #[default]
+    Stable,
+    Nightly,
+    Master,
+}
+
+impl ReleaseChannel {
+    /// GitHub repository publishing the channel's builds.
+    pub fn ytdlp_repository(&self) -> &'static str {
+        match self {
+            ReleaseChannel::Stable => YTDLP_GITHUB,
+            ReleaseChannel::Nightly => "yt-dlp/yt-dlp-nightly-builds",
+            ReleaseChannel::Master => "yt-dlp/yt-dlp-master-builds",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, TS)]
+#[ts(export)]
+pub struct YtdlpUpdateSettings {
+    #[serde(default)]
+    channel: ReleaseChannel,
+    /// Release tag to stay on, such as `2025.01.15`, instead of the channel's latest.
+    #[serde(default)]
+    pinned_tag: Option<String>,
+    /// Updates yt-dlp when the app starts.
+    #[serde(default)]
+    auto_update: bool,
+}
+
+impl YtdlpUpdateSettings {
+    pub fn repository(&self) -> &'static str {
+        self.channel.ytdlp_repository()
+    }
+
+    pub fn pinned_tag(&self) -> Option<&str> {
+        self.pinned_tag.as_deref()
+    }
+
+    pub fn auto_update(&self) -> bool {
+        self.auto_update
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, TS)]
+#[ts(export)]
+pub struct InstalledVersion {
+    repository: String,
+    tag: String,
+    /// Unix seconds.
+    #[ts(type = "number")]
+    installed_at: i64,
+}
+
+/// Installed releases by executable name.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InstalledVersions(HashMap<String, InstalledVersion>);
+
+impl InstalledVersions {
+    /// Reads the versions recorded in `dir`, binaries installed before they were
+    /// recorded have none.
+    pub fn load(dir: &Path) -> InstalledVersions {
+        match fs::read(dir.join(VERSIONS_FILENAME)) {
+            Ok(file_data) => serde_json::from_slice(&file_data).unwrap_or_else(|err| {
+                error!("parsing {}: {}", VERSIONS_FILENAME, err);
+                InstalledVersions::default()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => InstalledVersions::default(),
+            Err(err) => {
+                error!("reading {}: {}", VERSIONS_FILENAME, err);
+                InstalledVersions::default()
+            }
+        }
+    }
+
+    pub fn get(&self, executable_name: &str) -> Option<&InstalledVersion> {
+        self.0.get(executable_name)
+    }
+
+    /// Records that release `tag` of `repository` was installed into `dir`.
+    pub fn record(dir: &Path, executable_name: &str, repository: &str, tag: &str) -> Result<(), VscraperError> {
+        let mut versions = InstalledVersions::load(dir);
+        versions.0.insert(
+            executable_name.to_string(),
+            InstalledVersion {
+                repository: repository.to_string(),
+                tag: tag.to_string(),
+                installed_at: history::unix_now(),
+            },
+        );
+        fs::write(dir.join(VERSIONS_FILENAME), serde_json::to_string_pretty(&versions)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GithubAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    /// `sha256:<hash>`, GitHub only computes it for assets uploaded since mid 2025.
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct GithubRelease {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<GithubAsset>,
+}
+
+impl GithubRelease {
+    pub fn asset(&self, name: &str) -> Option<&GithubAsset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// Release `tag` of the GitHub `repository`, the newest one when `None`.
+pub async fn release(
+    network: &NetworkSettings,
+    repository: &str,
+    tag: Option<&str>,
+) -> Result<GithubRelease, VscraperError> {
+    let url = match tag {
+        Some(tag) => format!("{}/repos/{}/releases/tags/{}", GITHUB_API, repository, tag),
+        None => format!("{}/repos/{}/releases/latest", GITHUB_API, repository),
+    };
+    async {
+        network
+            .http_client(&url)?
+            .get(&url)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .map_err(VscraperError::from)
+    }
+    .await
+    .map_err(|err| {
+        VscraperError::Install(format!("fetching release {} of {}: {}", tag.unwrap_or("latest"), repository, err))
+    })
+}
+
+/// Tag of the newest release of the GitHub `repository`.
+pub async fn latest_release_tag(network: &NetworkSettings, repository: &str) -> Result<String, VscraperError> {
+    Ok(release(network, repository, None).await?.tag_name)
+}
+
+/// Installed yt-dlp release compared to the one the update settings ask for.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct UpdateCheck {
+    installed: Option<InstalledVersion>,
+    channel: ReleaseChannel,
+    /// The pinned tag, or the channel's latest release.
+    target_tag: String,
+    pinned: bool,
+    update_available: bool,
+}
+
+pub async fn check_ytdlp(config: &Config) -> Result<UpdateCheck, VscraperError> {
+    let settings = config.get_ytdlp_updates();
+    let source = config.get_install_sources().get(YTDLP_EXECUTABLE);
+    let repository = source.repository(settings.repository());
+    let target_tag = match settings.pinned_tag() {
+        Some(tag) => tag.to_string(),
+        None => source.latest_tag(config.get_network(), settings.repository()).await?,
+    };
+    let installed = InstalledVersions::load(&config.get_binary_path()).get(YTDLP_EXECUTABLE).cloned();
+    let update_available =
+        !matches!(&installed, Some(installed) if installed.repository == repository && installed.tag == target_tag);
+
+    Ok(UpdateCheck {
+        installed,
+        channel: settings.channel,
+        target_tag,
+        pinned: settings.pinned_tag.is_some(),
+        update_available,
+    })
+}
+
+/// Installs the release [`check_ytdlp`] picks when it is not installed yet.
+pub async fn update_ytdlp(config: &Config, task: &InstallTask) -> Result<Installed, VscraperError> {
+    task.phase(InstallPhase::ResolvingRelease)?;
+    let check = check_ytdlp(config).await?;
+    if !check.update_available {
+        info!("yt-dlp {} is up to date", check.target_tag);
+        return Ok(Installed::up_to_date(check.target_tag));
+    }
+    let repository = config.get_ytdlp_updates().repository();
+    components::install(repository, YTDLP_EXECUTABLE, config, Some(&check.target_tag), task).await
+}
+
+/// Runs [`update_ytdlp`] as an install of `installs`, reporting the outcome as
+/// [`Emission::YtdlpInstall`].
+pub fn spawn_ytdlp_update(installs: &InstallManager, config: Config) -> Result<(), VscraperError> {
+    installs.start(YTDLP_EXECUTABLE, Emission::YtdlpInstall, move |task| async move {
+        update_ytdlp(&config, &task).await
+    })
+}
+
+#[tauri::command]
+pub async fn check_ytdlp_update(state: State<'_, Arc<Mutex<AppState>>>) -> Result<UpdateCheck, VscraperError> {
+    let config = state.lock().await.get_config();
+    check_ytdlp(&config).await
+}
+
+#[tauri::command]
+pub async fn upgrade_ytdlp(
+    installs: State<'_, InstallManager>,
+    state: State<'_, Arc<Mutex<AppState>>>,
+) -> Result<(), VscraperError> {
+    let config = state.lock().await.get_config();
+    spawn_ytdlp_update(&installs, config)
+}
+
+#[tauri::command]
+pub async fn update_ytdlp_settings(
+    state: State<'_, Arc<Mutex<AppState>>>,
+    ytdlp_updates: YtdlpUpdateSettings,
+) -> Result<(), VscraperError> {
+    state.lock().await.set_ytdlp_updates(ytdlp_updates);
+    Ok(())
+}
+
+#[test]
+fn test_installed_versions_are_recorded() {
+    let dir = crate::testing::TempDir::new("versions");
+    assert!(InstalledVersions::load(&dir).get(YTDLP_EXECUTABLE).is_none());
+
+    InstalledVersions::record(&dir, YTDLP_EXECUTABLE, YTDLP_GITHUB, "2025.01.15").unwrap();
+    InstalledVersions::record(&dir, YTDLP_EXECUTABLE, ReleaseChannel::Nightly.ytdlp_repository(), "2025.01.20").unwrap();
+    let versions = InstalledVersions::load(&dir);
+    let installed = versions.get(YTDLP_EXECUTABLE).unwrap();
+    assert_eq!(installed.tag, "2025.01.20");
+    assert_eq!(installed.repository, "yt-dlp/yt-dlp-nightly-builds");
+
+    let settings: YtdlpUpdateSettings = serde_json::from_str(r#"{ "pinned_tag": "2024.12.23" }"#).unwrap();
+    assert_eq!((settings.repository(), settings.pinned_tag()), (YTDLP_GITHUB, Some("2024.12.23")));
+}
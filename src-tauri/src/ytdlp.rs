@@ -1,236 +1,317 @@
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::sync::mpsc::error::TryRecvError;
-use tokio::sync::{Mutex, mpsc};
 use std::future::Future;
-use std::path::PathBuf;
-use std::process::{ExitStatus, Stdio};
-use std::sync::Arc;
-use tauri::{Manager, Runtime, State};
-use tauri_plugin_log::log::{debug, error, info, trace};
-use tokio::io::{AsyncBufReadExt, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tauri::State;
+use tauri_plugin_log::log::error;
 use tokio::process::Command;
 
-use crate::app_state::AppState;
-use crate::emissions::Emission;
-use crate::emit_and_handle_result;
+use crate::audio::AudioOptions;
+use crate::auth::JobAuth;
+use crate::config::Config;
+use crate::container::Container;
+use crate::engine::DownloadEngine;
+use crate::error::VscraperError;
+use crate::mitigation::MitigationProfile;
+use crate::probe;
+use crate::progress;
+use crate::queue::{Job, JobId};
+use crate::retry::RetryPolicy;
 
-const YTDLP_DOWNLOAD_UPDATE_REGEX: &str = r"\[download\]\s+(\d+(?:\.\d+)?)%\s+of\s+~?\s+?(\d+(?:\.\d+)?[GMK]iB)\s+at\s+(\d+\.\d+(?:[GMK]i)?B\/s)\s+ETA\s+((\d+:\d+)|(?:Unknown))";
+/// Marks the line yt-dlp prints with the final path of a finished download.
+const OUTPUT_PATH_PREFIX: &str = "vscraper-filepath:";
 
-#[derive(Clone, Serialize)]
-struct DownloadProgress {
-    url: String,
-    percent: String,
-    size_downloaded: String,
-    speed: String,
-    eta: String,
-}
-
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct DownloadOptions {
-    #[serde(default = "default_container")]
-    container: String,
+    #[serde(default)]
+    container: Container,
     #[serde(default = "default_name_format")]
     name_format: String,
     url: String,
     #[serde(default = "default_quality")]
     quality: String,
+    /// Extracts audio only, `container` is ignored when set.
+    #[serde(default)]
+    audio: Option<AudioOptions>,
+    /// Name of the mitigation profile to use instead of the one configured for the site.
+    #[serde(default)]
+    mitigation: Option<String>,
+    /// Retry policy replacing the configured one for this job.
+    #[serde(default)]
+    retry: Option<RetryPolicy>,
     // YTDLP Options
 }
 
-fn default_container() -> String {
-    String::from("mp4")
+impl DownloadOptions {
+    pub fn new(
+        url: String,
+        quality: String,
+        container: Container,
+        name_format: String,
+        audio: Option<AudioOptions>,
+    ) -> DownloadOptions {
+        DownloadOptions {
+            container,
+            name_format,
+            url,
+            quality,
+            audio,
+            mitigation: None,
+            retry: None,
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The `-f` selector, audio mode skips fetching a video stream it would drop anyway.
+    fn format_selector(&self) -> &str {
+        match &self.audio {
+            Some(_) if self.quality == default_quality() => "bestaudio/best",
+            _ => &self.quality,
+        }
+    }
+
+    /// Postprocessing arguments producing either the audio file or the video container.
+    fn output_args(&self) -> Vec<String> {
+        match &self.audio {
+            Some(audio) => audio.ytdlp_args(),
+            None => self.container.ytdlp_args(),
+        }
+    }
+
+    /// The mitigation profile this job asked for, overriding the per-domain default.
+    pub fn mitigation(&self) -> Option<&str> {
+        self.mitigation.as_deref()
+    }
+
+    pub fn with_mitigation(self, mitigation: Option<String>) -> DownloadOptions {
+        DownloadOptions { mitigation, ..self }
+    }
+
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        self.retry.as_ref()
+    }
+
+    pub fn with_retry(self, retry: Option<RetryPolicy>) -> DownloadOptions {
+        DownloadOptions { retry, ..self }
+    }
+
+    /// Copy of these options for another URL, used for playlist entries.
+    pub fn with_url(&self, url: &str) -> DownloadOptions {
+        DownloadOptions {
+            url: String::from(url),
+            ..self.clone()
+        }
+    }
+
+    /// Rejects audio options ffmpeg cannot produce. Unlike [`validate_options`] this
+    /// needs no probe.
+    pub fn validate_audio(&self) -> Result<(), VscraperError> {
+        match &self.audio {
+            Some(audio) => audio.validate().map_err(VscraperError::InvalidAudioOptions),
+            None => Ok(()),
+        }
+    }
+
+    /// The options the frontend gets when it sends nothing but `url`.
+    #[cfg(test)]
+    pub fn for_url(url: &str) -> DownloadOptions {
+        DownloadOptions::new(url.to_string(), default_quality(), Container::default(), default_name_format(), None)
+    }
 }
 
-fn default_name_format() -> String {
+/// yt-dlp format selectors that never name a concrete format id.
+const FORMAT_SELECTOR_KEYWORDS: [&str; 14] = [
+    "best", "worst", "bestvideo", "bestaudio", "worstvideo", "worstaudio", "b", "w", "bv", "ba", "wv", "wa", "all",
+    "mergeall",
+];
+
+pub fn default_name_format() -> String {
     String::from("%(title)s.%(ext)s")
 }
 
-fn default_quality() -> String {
+pub fn default_quality() -> String {
     String::from("best")
 }
 
+/// Lowercase host of `url` without a leading `www.`.
+pub fn url_host(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    match host.is_empty() {
+        true => None,
+        false => Some(host.trim_start_matches("www.").to_lowercase()),
+    }
+}
+
+/// Whether `host` is `site` or one of its subdomains.
+pub fn host_matches(host: &str, site: &str) -> bool {
+    host == site || host.ends_with(&format!(".{}", site))
+}
+
 #[tauri::command]
-pub async fn download_from_options<R: Runtime>(
-    app_handle: tauri::AppHandle<R>,
+pub async fn download_from_options(
+    engine: State<'_, DownloadEngine>,
     options: DownloadOptions,
-) -> tauri::Result<()> {
-    tauri::async_runtime::spawn(async move {
-        let state: State<'_, Arc<Mutex<AppState>>> = app_handle.state();
-        let config = state.lock().await.get_config();
-        let ytdlp_path = config.get_ytdlp_path();
-        let ffmpeg_path = config.get_ffmpeg_path();
-        let (tx, mut rx) = mpsc::channel(100); // Used to communicate kill and pause.
-        state.lock().await.add_download(options.url.clone(), tx);
-
-        debug!("checking url availability for: {}", options.url);
-        match check_url_availability(&ytdlp_path, &ffmpeg_path, &options).await {
-            Ok(exit_status) => {
-                if exit_status.success() {
-                    // TODO: Parse stderr to provide exact error caused by yt-dlp.
-
-                    // Return generic error in place of other errors
-                } else {
-
-                }
-                emit_and_handle_result(&app_handle, Emission::YtdlpUrlUpdate, exit_status.success());
-            }
-            Err(err) => match err.kind() {
-                err => error!("executing command: {}", err),
-            },
-        }
+) -> Result<JobId, VscraperError> {
+    engine.enqueue(options).await
+}
 
-        let download_path = app_handle.path().download_dir().unwrap().join(&options.name_format);
-
-        debug!("downloading from url");
-        let mut child = Command::new(&ytdlp_path)
-            .arg("--newline")
-            .arg("--ffmpeg-location")
-            .arg(&ffmpeg_path)
-            .arg("-f")
-            .arg(options.quality)
-            .arg("-o")
-            .arg(download_path)
-            .arg(options.url.clone())
-            .stderr(Stdio::null())
-            .stdout(Stdio::piped())
-            .spawn()
-            .unwrap();
-
-        debug!("spawned ytdlp download from url: {}, with pid: {}", options.url, child.id().map_or("unknown".to_string(), |code| code.to_string()));
-
-        let stderr = child.stdout.take().unwrap();
-        let mut reader = BufReader::new(stderr).lines();
-
-        let regex = Regex::new(YTDLP_DOWNLOAD_UPDATE_REGEX).unwrap();
-        while let Ok(Some(line)) = reader.next_line().await {
-            trace!("ytdlp: {}", line);
-            match rx.try_recv() {
-                Ok(_) | Err(TryRecvError::Disconnected) => {
-                    let pid = child.id().map_or("unknown".to_string(), |code| code.to_string());
-                    debug!("received kill signal for url: {}, pid: {}", options.url, pid);
-                    match child.kill().await {
-                        Ok(_) => {
-                            info!("successfully killed child for url: {}, pid: {}", options.url, pid);
-                            match child.wait().await {
-                                Ok(exit_status) => {
-                                    debug!("killed zombie child for url: {}, pid: {}, exit code: {}", options.url, pid, exit_status);
-                                },
-                                Err(err) => {
-                                    error!("failed to kill zombie child for url: {}, pid: {}, err: {}", options.url, pid, err);
-                                },
-                            }
-                        },
-                        Err(err) => error!("failed to kill child for url: {}, pid: {} err: {}", options.url, pid, err),
-                    }
-                    break
-                },
-                Err(TryRecvError::Empty) => {},
-            }
-            if regex.is_match(&line) {
-                if let Some(captures) = regex.captures(&line) {
-                    let url = options.url.clone();
-                    let percent = String::from(&captures[1]);
-                    let size_downloaded = String::from(&captures[2]);
-                    let speed = String::from(&captures[3]);
-                    let eta = String::from(&captures[4]);
-
-                    emit_and_handle_result(
-                        &app_handle, 
-                        Emission::YtdlpDownloadUpdate, 
-                        DownloadProgress {
-                            url,
-                            percent,
-                            size_downloaded,
-                            speed,
-                            eta,
-                        }
-                    );
-                }
-            }
-        }
+/// The yt-dlp invocation downloading `job` into `download_dir`, with `resume` continuing
+/// the partial file of an earlier run.
+pub fn download_command(
+    config: &Config,
+    download_dir: &Path,
+    job: &Job,
+    auth: &JobAuth,
+    mitigation: &MitigationProfile,
+    resume: bool,
+) -> Command {
+    let options = job.options();
+    let mut command = Command::new(config.get_ytdlp_path());
+    command
+        .args(auth.args())
+        .args(mitigation.ytdlp_args())
+        .args(config.get_network().ytdlp_args(&options.url));
+    if resume {
+        // Reuse the `.part` file left behind by the stopped child.
+        command.arg("--continue");
+    }
+    if job.playlist_id().is_some() {
+        // Entries such as `watch?v=..&list=..` would otherwise fetch the whole playlist again.
+        command.arg("--no-playlist");
+    }
+    command
+        .arg("--newline")
+        // `--print` implies `--quiet`, so progress has to be requested explicitly.
+        .arg("--progress")
+        .args(progress::progress_template_args())
+        .arg("--print")
+        .arg(format!("after_move:{}%(filepath)s", OUTPUT_PATH_PREFIX))
+        .arg("--ffmpeg-location")
+        .arg(config.get_ffmpeg_path())
+        .arg("-f")
+        .arg(options.format_selector())
+        .args(options.output_args())
+        .arg("-o")
+        .arg(download_dir.join(&options.name_format))
+        .arg(&options.url);
+    command
+}
 
-        match child.wait().await {
-            Ok(status) => {
-                emit_and_handle_result(
-                    &app_handle, 
-                    Emission::YtdlpDownloadFinish, 
-                    status.success()
-                );
-            },
-            Err(err) => error!("download with url: {}, failed with err: {}", options.url, err),
-        }
-    }).await?;
+/// The final path yt-dlp prints for a finished download.
+pub fn parse_output_path(line: &str) -> Option<PathBuf> {
+    line.strip_prefix(OUTPUT_PATH_PREFIX).map(PathBuf::from)
+}
 
-    Ok(())
+#[tauri::command]
+pub async fn cancel_download(engine: State<'_, DownloadEngine>, job_id: JobId) -> Result<(), VscraperError> {
+    engine.cancel(job_id).await
 }
 
 #[tauri::command]
-pub async fn cancel_download(app_handle: tauri::AppHandle, url: String) {
-    let state: State<'_, Arc<Mutex<AppState>>> = app_handle.state();
-    let app_state = state.lock().await;
-    let tx = app_state.get_download(&url);
-    match tx {
-        Some(tx) => {
-            emit_and_handle_result(
-                &app_handle, 
-                Emission::YtdlpCancelDownload, 
-                tx.send(()).await.is_ok()
-            );
-        }
-        None => {
-            error!("no download with url: {}", url);
+pub async fn pause_download(engine: State<'_, DownloadEngine>, job_id: JobId) -> Result<bool, VscraperError> {
+    Ok(engine.pause(&job_id).await)
+}
+
+#[tauri::command]
+pub async fn resume_download(engine: State<'_, DownloadEngine>, job_id: JobId) -> Result<bool, VscraperError> {
+    Ok(engine.resume(&job_id).await)
+}
+
+/// Fails on invalid audio settings, or when `options.quality` names concrete format ids
+/// that cannot be muxed into `options.container`. The probe is authenticated with `auth`,
+/// its errors are left for the download itself to report.
+pub async fn validate_options(config: &Config, options: &DownloadOptions, auth: &JobAuth) -> Result<(), VscraperError> {
+    options.validate_audio()?;
+    if options.audio.is_some() {
+        return Ok(());
+    }
+
+    let names_format_ids = options.quality.split('+').all(|format_id| {
+        !format_id.is_empty()
+            && !FORMAT_SELECTOR_KEYWORDS.contains(&format_id)
+            && format_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    });
+    if !names_format_ids {
+        return Ok(());
+    }
+
+    let mitigation = config.get_mitigation().profile_for(options);
+    match probe::probe(config, &options.url, auth, &mitigation).await {
+        Ok(info) => Ok(options.container.check_formats(&options.quality, &info.formats)?),
+        Err(err) => {
+            error!("checking container for url: {}, err: {}", options.url, err);
+            Ok(())
         }
     }
 }
 
-fn check_url_availability(
-    ytdlp_path: &PathBuf, 
-    ffmpeg_path: &PathBuf, 
-    options: &DownloadOptions
-) -> impl Future<Output = Result<ExitStatus, std::io::Error>> {
-    Command::new(&ytdlp_path)
+pub fn check_url_availability(
+    config: &Config,
+    options: &DownloadOptions,
+    auth: &JobAuth,
+    mitigation: &MitigationProfile,
+) -> impl Future<Output = Result<Output, std::io::Error>> {
+    Command::new(config.get_ytdlp_path())
+        .args(auth.args())
+        .args(mitigation.ytdlp_args())
+        .args(config.get_network().ytdlp_args(&options.url))
         .arg("--ffmpeg-location")
-        .arg(&ffmpeg_path)
+        .arg(config.get_ffmpeg_path())
         .arg("--simulate")
         .arg(&options.url)
         .stderr(Stdio::piped())
-        .stdout(Stdio::piped())
-        .status()
+        .stdout(Stdio::null())
+        .output()
 }
 
 #[tauri::command]
-pub async fn download_best_quality<R: Runtime>(
-    app_handle: tauri::AppHandle<R>,
+pub async fn download_best_quality(
+    engine: State<'_, DownloadEngine>,
     options: DownloadOptions,
-) -> tauri::Result<()> {
-    //
+) -> Result<JobId, VscraperError> {
+    // `bestvideo` alone would drop the audio stream.
+    let quality = match options.audio {
+        Some(_) => String::from("bestaudio/best"),
+        None => String::from("bestvideo*+bestaudio/best"),
+    };
     download_from_options(
-        app_handle, 
+        engine,
         DownloadOptions {
-            quality: String::from("bestvideo"),
+            quality,
             ..options
         }
     ).await
-}        
-
-// #[test]
-// fn test_ytdlp_custom_format_invalid_url() {
-//     tauri::async_runtime::block_on(async {
-//         use tauri::Manager;
-//         let url = String::from(
-//             "htt://www.youtube.com/watch?v=dQw4w9WgXcQ&list=RDdQw4w9WgXcQ&start_radio=1",
-//         );
-//         let app = tauri::test::mock_app();
-//         let result = download_from_options(
-//             app.app_handle().clone(),
-//             DownloadOptions { 
-//                 url,
-//                 container: default_container(),
-//                 name_format: default_name_format(),
-//                 quality: default_quality(),
-//             }
-//         ).await;
-//         assert_eq!(result.is_ok(), false);
-//     });
-// }
+}
+
+#[test]
+fn test_download_command_routes_each_job() {
+    use crate::queue::DownloadQueue;
+
+    let config: Config = serde_json::from_value(serde_json::json!({
+        "network": {
+            "proxy": "http://127.0.0.1:8080",
+            "routes": { "vimeo.com": "direct" },
+            "source_address": "10.0.0.2",
+        },
+    }))
+    .unwrap();
+    let mut queue = DownloadQueue::default();
+    let proxied = queue.enqueue(DownloadOptions::for_url("https://www.youtube.com/watch?v=1"), None);
+    let direct = queue.enqueue(DownloadOptions::for_url("https://vimeo.com/1"), None);
+
+    // Routing is passed to each child, jobs for differently routed sites can run side by side.
+    let args = |id: &JobId| -> Vec<String> {
+        let job = queue.get(id).unwrap();
+        let (auth, mitigation) = (JobAuth::default(), MitigationProfile::default());
+        let command = download_command(&config, Path::new("/downloads"), job, &auth, &mitigation, false);
+        command.as_std().get_args().map(|arg| arg.to_string_lossy().into_owned()).collect()
+    };
+    let routing = |args: &[String]| -> Vec<String> { args[..4].to_vec() };
+    assert_eq!(routing(&args(&proxied)), ["--proxy", "http://127.0.0.1:8080", "--source-address", "10.0.0.2"]);
+    assert_eq!(routing(&args(&direct)), ["--proxy", "", "--source-address", "10.0.0.2"]);
+}
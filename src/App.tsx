@@ -2,9 +2,8 @@ import { Button } from "@/components/ui/button";
 import { Checkbox } from "@/components/ui/checkbox";
 import { Input } from "@/components/ui/input";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "@/components/ui/select";
-import { Config, DownloadProgress } from '@/types';
+import { CommandError, Config, DownloadFailure, DownloadProgress, Job, JobId, UrlChecked } from '@/types';
 import { invoke } from '@tauri-apps/api/core';
-import { listen } from '@tauri-apps/api/event';
 import { debug } from '@tauri-apps/plugin-log';
 import { Link2, X } from 'lucide-react';
 import { useEffect, useState } from 'react';
@@ -12,6 +11,8 @@ import { Navigate, useNavigate } from 'react-router';
 import { Label } from "./components/ui/label";
 import { Progress } from "@radix-ui/react-progress";
 import TextInputWithButton from "./components/TextInputWithButton";
+import { listenEvent } from "./lib/events";
+import { formatBytes, formatEta } from "./lib/utils";
 
 const config: Config = await invoke('get_config');
 
@@ -19,11 +20,12 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
     const [url, setUrl] = useState('');
     const navigate = useNavigate();
     const [quality, setQuality] = useState('Best');
-    const [format, setFormat] = useState('MP4');
+    const [container, setContainer] = useState('mp4');
     const [isAdvancedOptionsOpen, setIsAdvancedOptionsOpen] = useState(false);
     const [downloads, setDownloads] = useState<{ [key: string]: DownloadProgress }>({});
     const [isDownloading, setIsDownloading] = useState(false);
     const [downloadError, setDownloadError] = useState<string | null>(null);
+    const [interruptedJobs, setInterruptedJobs] = useState<Job[]>([]);
     const [advancedOptions, setAdvancedOptions] = useState({
         autoStart: 'Yes',
         downloadFolder: 'Default',
@@ -39,22 +41,26 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
     }
 
     useEffect(() => {
-        const downloadUpdateListener = listen<DownloadProgress>('ytdlp_download_update', (event) => {
-            const { url, percent, size_downloaded, speed, eta } = event.payload;
+        // Downloads that were running when the app last closed wait for the user.
+        invoke<Job[]>('list_downloads')
+            .then(jobs => setInterruptedJobs(jobs.filter(job => job.state === 'interrupted')))
+            .catch(error => console.error('Listing downloads failed:', error));
+
+        const downloadUpdateListener = listenEvent<DownloadProgress>('ytdlp_download_update', (progress) => {
             setDownloads(prev => ({
                 ...prev,
-                [url]: { url, percent, size_downloaded, speed, eta }
+                [progress.job_id]: progress
             }));
         });
 
-        const downloadSuccessListener = listen<string>('ytdlp_url_update', (event) => {
+        const downloadSuccessListener = listenEvent<UrlChecked>('ytdlp_url_update', () => {
             setIsDownloading(false);
             setDownloadError(null);
         });
 
-        const downloadErrorListener = listen<string>('ytdlp_url_error', (event) => {
+        const downloadErrorListener = listenEvent<DownloadFailure>('ytdlp_download_error', (failure) => {
             setIsDownloading(false);
-            setDownloadError(event.payload);
+            setDownloadError(`${failure.error.replace(/_/g, ' ')}: ${failure.stderr_tail.at(-1) ?? failure.url}`);
         });
 
         return () => {
@@ -74,27 +80,36 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
             const options = {
                 url,
                 quality,
-                format,
+                container,
                 ...advancedOptions
             };
             await invoke('download_best_quality', { options });
         } catch (error) {
             console.error('Download failed:', error);
             setIsDownloading(false);
-            setDownloadError('Failed to start download');
+            setDownloadError((error as CommandError).message ?? 'Failed to start download');
         }
     };
 
-    const cancelDownload = async (downloadUrl: string) => {
+    const cancelDownload = async (jobId: JobId) => {
         try {
-            await invoke('cancel_download', { url: downloadUrl });
-            const { [downloadUrl]: _, ...remainingDownloads } = downloads;
+            await invoke('cancel_download', { jobId });
+            const { [jobId]: _, ...remainingDownloads } = downloads;
             setDownloads(remainingDownloads);
         } catch (error) {
             console.error('Cancel download failed:', error);
         }
     };
 
+    const handleInterrupted = async (jobId: JobId, resume: boolean) => {
+        try {
+            await invoke(resume ? 'resume_download' : 'cancel_download', { jobId });
+        } catch (error) {
+            console.error('Handling interrupted download failed:', error);
+        }
+        setInterruptedJobs(prev => prev.filter(job => job.id !== jobId));
+    };
+
     return (
         <main className="flex flex-col items-center justify-center text-center min-h-screen max-w-md mx-auto space-y-4">
             <div className="flex space-x-2">
@@ -119,6 +134,19 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
                 </div>
             )}
 
+            {interruptedJobs.length > 0 && (
+                <div className="w-full space-y-2 text-sm">
+                    <Label>Interrupted downloads</Label>
+                    {interruptedJobs.map(job => (
+                        <div key={job.id} className="flex items-center space-x-2">
+                            <span className="grow truncate text-left">{job.options.url}</span>
+                            <Button size="sm" onClick={() => handleInterrupted(job.id, true)}>Resume</Button>
+                            <Button size="sm" variant="outline" onClick={() => handleInterrupted(job.id, false)}>Discard</Button>
+                        </div>
+                    ))}
+                </div>
+            )}
+
             <div className="flex space-x-2">
                 <Select value={quality} onValueChange={setQuality} disabled={isDownloading}>
                     <SelectTrigger className="w-full">
@@ -132,15 +160,15 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
                     </SelectContent>
                 </Select>
 
-                <Select value={format} onValueChange={setFormat} disabled={isDownloading}>
+                <Select value={container} onValueChange={setContainer} disabled={isDownloading}>
                     <SelectTrigger className="w-full">
                         <SelectValue placeholder="Format" />
                     </SelectTrigger>
                     <SelectContent>
-                        <SelectItem value="MP4">MP4</SelectItem>
-                        <SelectItem value="MKV">MKV</SelectItem>
-                        <SelectItem value="AVI">AVI</SelectItem>
-                        <SelectItem value="WebM">WebM</SelectItem>
+                        <SelectItem value="mp4">MP4</SelectItem>
+                        <SelectItem value="mkv">MKV</SelectItem>
+                        <SelectItem value="webm">WebM</SelectItem>
+                        <SelectItem value="mov">MOV</SelectItem>
                     </SelectContent>
                 </Select>
             </div>
@@ -253,32 +281,32 @@ const DownloadPage = ({ hasSeenHomepage }: { hasSeenHomepage: boolean }) => {
                 <div className="mt-4">
                     <h3 className="text-lg font-semibold mb-2">Downloading</h3>
                     <div className="space-y-2">
-                        {Object.entries(downloads).map(([url, download]) => (
+                        {Object.entries(downloads).map(([jobId, download]) => (
                             <div
-                                key={url}
+                                key={jobId}
                                 className="flex items-center space-x-2 p-2 border rounded-md"
                             >
                                 <div className="grow">
                                     <div className="flex justify-between">
-                                        <span className="text-sm truncate max-w-[200px]">{url}</span>
-                                        <span className="text-sm">{download.percent}%</span>
+                                        <span className="text-sm truncate max-w-[200px]">{download.url}</span>
+                                        <span className="text-sm">{download.percent?.toFixed(1) ?? "?"}%</span>
                                     </div>
                                     <div className="w-full bg-gray-200 rounded-full h-2.5 dark:bg-gray-700 mt-1">
                                         <Progress
                                             className="bg-blue-600 h-2.5 rounded-full"
-                                            value={Number(download.percent)}
+                                            value={download.percent ?? 0}
                                         ></Progress>
                                     </div>
                                     <div className="flex justify-between text-xs text-gray-500 mt-1">
-                                        <span>{download.speed}</span>
-                                        <span>ETA: {download.eta}</span>
+                                        <span>{formatBytes(download.speed)}/s</span>
+                                        <span>ETA: {formatEta(download.eta)}</span>
                                     </div>
                                 </div>
                                 <div className="flex space-x-2">
                                     <Button
                                         variant="ghost"
                                         size="icon"
-                                        onClick={() => cancelDownload(url)}
+                                        onClick={() => cancelDownload(jobId)}
                                     >
                                         <X className="h-4 w-4" />
                                     </Button>
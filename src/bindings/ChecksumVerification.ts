@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A downloaded asset compared to the hash its release published.
+ */
+export type ChecksumVerification = { asset: string, expected: string, actual: string, verified: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where the path a dependency runs from came from.
+ */
+export type DependencySource = "managed" | "path" | "configured";
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Dependency } from "./Dependency";
+import type { DependencySource } from "./DependencySource";
+
+export type DependencyStatus = { dependency: Dependency, path: string, source: DependencySource, found: boolean, executable: boolean, 
+/**
+ * First line of the `--version` output, `None` when the binary did not run.
+ */
+version: string | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why yt-dlp failed, classified from its stderr.
+ */
+export type DownloadError = "unsupported_url" | "geo_blocked" | "unavailable" | "login_required" | "age_restricted" | "bot_challenge" | "rate_limited" | "ffmpeg_missing" | "disk_full" | "network" | "unknown";
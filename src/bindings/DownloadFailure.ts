@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DownloadError } from "./DownloadError";
+import type { JobId } from "./JobId";
+
+/**
+ * Payload sent to the frontend when a job fails.
+ */
+export type DownloadFailure = { job_id: JobId, url: string, error: DownloadError, exit_code: number | null, stderr_tail: Array<string>, };
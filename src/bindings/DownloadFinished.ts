@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DownloadError } from "./DownloadError";
+import type { JobId } from "./JobId";
+import type { JobState } from "./JobState";
+
+/**
+ * Payload of [`Emission::YtdlpDownloadFinish`], sent once for every job that was started.
+ */
+export type DownloadFinished = { job_id: JobId, url: string, 
+/**
+ * Finished, failed or cancelled.
+ */
+state: JobState, exit_code: number | null, output_path: string | null, 
+/**
+ * Milliseconds since the job was started, pauses included.
+ */
+duration_ms: number, 
+/**
+ * Set when the job failed, the details come with [`Emission::YtdlpDownloadError`].
+ */
+error: DownloadError | null, };
@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DownloadPhase } from "./DownloadPhase";
+import type { JobId } from "./JobId";
+
+export type DownloadProgress = { job_id: JobId, url: string, phase: DownloadPhase, percent: number | null, downloaded_bytes: number | null, total_bytes: number | null, 
+/**
+ * Bytes per second.
+ */
+speed: number | null, 
+/**
+ * Seconds.
+ */
+eta: number | null, fragment_index: number | null, fragment_count: number | null, 
+/**
+ * Name of the running yt-dlp postprocessor, such as `Merger`.
+ */
+postprocessor: string | null, 
+/**
+ * Starts at 1 and counts the retries of a failed download.
+ */
+attempt: number, };
@@ -0,0 +1,3 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type Emission = "ffmpeg_install" | "install_progress" | "ytdlp_cancel_download" | "ytdlp_download_error" | "ytdlp_download_update" | "ytdlp_install" | "ytdlp_pause_download" | "ytdlp_playlist_update" | "ytdlp_resume_download" | "ytdlp_url_update" | "ytdlp_download_finish";
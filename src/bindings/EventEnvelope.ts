@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Every payload the webview receives is wrapped in this.
+ */
+export type EventEnvelope<T> = { version: number, payload: T, };
@@ -0,0 +1,3 @@
+// This file was generated by `cargo test`. Do not edit this file manually.
+
+export const EVENT_SCHEMA_VERSION = 6;
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why an install failed, `kind` is one of the kinds commands fail with.
+ */
+export type InstallFailure = { kind: string, message: string, };
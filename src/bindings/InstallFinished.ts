@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ChecksumVerification } from "./ChecksumVerification";
+import type { InstallFailure } from "./InstallFailure";
+
+/**
+ * Payload of [`Emission::YtdlpInstall`] and [`Emission::FfmpegInstall`].
+ */
+export type InstallFinished = { executable: string, 
+/**
+ * Release tag installed now, `None` when the install failed.
+ */
+version: string | null, 
+/**
+ * The downloaded binary checked against its release, `None` when nothing was
+ * downloaded or the install failed before the check.
+ */
+checksum: ChecksumVerification | null, 
+/**
+ * `None` on success, kind `install_cancelled` when the install was cancelled.
+ */
+error: InstallFailure | null, };
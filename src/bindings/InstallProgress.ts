@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstallPhase } from "./InstallPhase";
+
+/**
+ * Payload of [`Emission::InstallProgress`], sent on every phase change and a few times a
+ * second while downloading.
+ */
+export type InstallProgress = { executable: string, phase: InstallPhase, downloaded_bytes: number, 
+/**
+ * `None` when the server does not send a length.
+ */
+total_bytes: number | null, };
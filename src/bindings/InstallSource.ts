@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Where a component is installed from.
+ */
+export type InstallSource = { "type": "github" } | { "type": "mirror", base_url: string, } | { "type": "local", path: string, 
+/**
+ * Checked instead of a `SHA2-256SUMS` or `<file>.sha256` next to the file.
+ */
+sha256: string | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstallSource } from "./InstallSource";
+
+/**
+ * Install source of each component.
+ */
+export type InstallSources = { ytdlp: InstallSource, ffmpeg: InstallSource, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type InstalledVersion = { repository: string, tag: string, 
+/**
+ * Unix seconds.
+ */
+installed_at: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Identifies one job for its whole life, so the same URL can be queued more than once.
+ */
+export type JobId = string;
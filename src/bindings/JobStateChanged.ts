@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JobId } from "./JobId";
+import type { JobState } from "./JobState";
+
+/**
+ * Payload of [`Emission::YtdlpPauseDownload`], [`Emission::YtdlpResumeDownload`] and
+ * [`Emission::YtdlpCancelDownload`].
+ */
+export type JobStateChanged = { job_id: JobId, url: string, state: JobState, };
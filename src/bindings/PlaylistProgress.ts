@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Number of jobs of a playlist in each state.
+ */
+export type PlaylistProgress = { playlist_id: string, total: number, queued: number, running: number, paused: number, finished: number, failed: number, cancelled: number, interrupted: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which yt-dlp builds to follow, extractor fixes land in nightly and master days
+ * before a stable release.
+ */
+export type ReleaseChannel = "stable" | "nightly" | "master";
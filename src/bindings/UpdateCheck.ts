@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InstalledVersion } from "./InstalledVersion";
+import type { ReleaseChannel } from "./ReleaseChannel";
+
+/**
+ * Installed yt-dlp release compared to the one the update settings ask for.
+ */
+export type UpdateCheck = { installed: InstalledVersion | null, channel: ReleaseChannel, 
+/**
+ * The pinned tag, or the channel's latest release.
+ */
+target_tag: string, pinned: boolean, update_available: boolean, };
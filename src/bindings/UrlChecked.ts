@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { JobId } from "./JobId";
+
+/**
+ * Payload of [`Emission::YtdlpUrlUpdate`], sent once yt-dlp has checked the job's URL.
+ */
+export type UrlChecked = { job_id: JobId, url: string, available: boolean, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ReleaseChannel } from "./ReleaseChannel";
+
+export type YtdlpUpdateSettings = { channel: ReleaseChannel, 
+/**
+ * Release tag to stay on, such as `2025.01.15`, instead of the channel's latest.
+ */
+pinned_tag: string | null, 
+/**
+ * Updates yt-dlp when the app starts.
+ */
+auto_update: boolean, };
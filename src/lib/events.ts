@@ -0,0 +1,14 @@
+import { EVENT_SCHEMA_VERSION, type Emission, type EventEnvelope } from "@/types"
+import { listen } from "@tauri-apps/api/event"
+import { warn } from "@tauri-apps/plugin-log"
+
+// Unwraps the payload of `emission`, dropping events from a schema this build does not know.
+export function listenEvent<T>(emission: Emission, handler: (payload: T) => void) {
+  return listen<EventEnvelope<T>>(emission, (event) => {
+    if (event.payload.version !== EVENT_SCHEMA_VERSION) {
+      warn(`ignoring ${emission} event with schema version ${event.payload.version}, expected ${EVENT_SCHEMA_VERSION}`)
+      return
+    }
+    handler(event.payload.payload)
+  })
+}
@@ -4,3 +4,20 @@ import { twMerge } from "tailwind-merge"
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+export function formatBytes(bytes: number | null) {
+  if (bytes === null) return "Unknown"
+  const units = ["B", "KiB", "MiB", "GiB", "TiB"]
+  let unit = 0
+  while (bytes >= 1024 && unit < units.length - 1) {
+    bytes /= 1024
+    unit++
+  }
+  return `${bytes.toFixed(unit === 0 ? 0 : 2)}${units[unit]}`
+}
+
+export function formatEta(seconds: number | null) {
+  if (seconds === null) return "Unknown"
+  const minutes = Math.floor(seconds / 60)
+  return `${String(minutes).padStart(2, "0")}:${String(seconds % 60).padStart(2, "0")}`
+}
@@ -1,18 +1,178 @@
-export interface Config {
-    skip_homepage: boolean,
-}
-
-export interface DownloadProgress {
-    url: string,
-    percent: string,
-    size_downloaded: string,
-    speed: string,
-    eta: string,
-}
-
-export enum Emission {
-    FfmpegInstall = "ffmpeg_install",
-    YtdlpDownloadUpdate = "ytdlp_download_update",
-    YtdlpInstall = "ytdlp_install",
-    YtdlpUrlSuccess = "ytdlp_url_success",
-}
\ No newline at end of file
+import type { DownloadError } from "./bindings/DownloadError";
+import type { JobId } from "./bindings/JobId";
+import type { JobState } from "./bindings/JobState";
+
+// Event payloads are generated from the Rust types by `cargo test`, see `src-tauri/src/emissions.rs`.
+export type { ChecksumVerification } from "./bindings/ChecksumVerification";
+export type { DownloadFailure } from "./bindings/DownloadFailure";
+export type { DownloadFinished } from "./bindings/DownloadFinished";
+export type { DownloadPhase } from "./bindings/DownloadPhase";
+export type { DownloadProgress } from "./bindings/DownloadProgress";
+export type { Emission } from "./bindings/Emission";
+export type { EventEnvelope } from "./bindings/EventEnvelope";
+export { EVENT_SCHEMA_VERSION } from "./bindings/EventSchemaVersion";
+export type { InstallFailure } from "./bindings/InstallFailure";
+export type { InstallFinished } from "./bindings/InstallFinished";
+export type { InstallPhase } from "./bindings/InstallPhase";
+export type { InstallProgress } from "./bindings/InstallProgress";
+export type { JobStateChanged } from "./bindings/JobStateChanged";
+export type { PlaylistProgress } from "./bindings/PlaylistProgress";
+export type { UrlChecked } from "./bindings/UrlChecked";
+// Returned by the yt-dlp update commands, see `src-tauri/src/versions.rs`.
+export type { InstalledVersion } from "./bindings/InstalledVersion";
+export type { ReleaseChannel } from "./bindings/ReleaseChannel";
+export type { UpdateCheck } from "./bindings/UpdateCheck";
+export type { YtdlpUpdateSettings } from "./bindings/YtdlpUpdateSettings";
+// Passed to `update_install_sources`, see `src-tauri/src/install_source.rs`.
+export type { InstallSource } from "./bindings/InstallSource";
+export type { InstallSources } from "./bindings/InstallSources";
+// Returned by `check_dependencies`, see `src-tauri/src/dependencies.rs`.
+export type { Dependency } from "./bindings/Dependency";
+export type { DependencySource } from "./bindings/DependencySource";
+export type { DependencyStatus } from "./bindings/DependencyStatus";
+export type { DownloadError, JobId, JobState };
+
+export interface Config {
+    history_path: string | null,
+    max_parallel_downloads: number,
+    skip_homepage: boolean,
+    use_system_binaries: boolean,
+}
+
+export type Container = "mp4" | "mkv" | "webm" | "mov";
+
+export type AudioCodec = "mp3" | "m4a" | "opus" | "flac" | "wav";
+
+export interface AudioOptions {
+    codec: AudioCodec,
+    quality: string,
+    embed_thumbnail: boolean,
+    embed_metadata: boolean,
+}
+
+export interface CommandError {
+    kind: "io" | "tauri" | "json" | "history" | "container" | "invalid_audio_options" | "ytdlp" | "download_not_found" | "install" | "auth" | "network" | "mitigation" | "http" | "checksum" | "install_cancelled",
+    message: string,
+}
+
+/** Secrets are encrypted by the backend and never sent back. */
+export type AuthInput =
+    | { type: "cookies_file", path: string }
+    | { type: "password", username: string, password: string }
+    | { type: "netrc", machine: string, login: string, password: string }
+    | { type: "browser", browser: string, profile: string | null };
+
+export interface AuthProfileSummary {
+    site: string,
+    method: "cookies" | "password" | "netrc" | "browser",
+    account: string | null,
+}
+
+export interface MitigationProfile {
+    name: string,
+    sleep_requests: number | null,
+    min_sleep_interval: number | null,
+    max_sleep_interval: number | null,
+    playlist_sleep: number | null,
+    limit_rate: string | null,
+    user_agent: string | null,
+    headers: string[],
+    retries: number | null,
+    fragment_retries: number | null,
+    retry_sleep: string | null,
+    extractor_args: string[],
+    escalate_to: string | null,
+}
+
+export interface MitigationSettings {
+    profiles: MitigationProfile[],
+    default_profile: string,
+    domains: Record<string, string>,
+}
+
+export interface NetworkSettings {
+    /** http, https, socks5 or socks5h URL. */
+    proxy: string | null,
+    routes: Record<string, "proxy" | "direct">,
+    source_address: string | null,
+}
+
+export interface RetryPolicy {
+    /** Runs in total, the first one included. */
+    max_attempts: number,
+    initial_delay_ms: number,
+    max_delay_ms: number,
+    multiplier: number,
+    retryable: DownloadError[],
+}
+
+export interface Job {
+    id: JobId,
+    options: {
+        url: string,
+        container: Container,
+        name_format: string,
+        quality: string,
+        audio: AudioOptions | null,
+        mitigation: string | null,
+        retry: RetryPolicy | null,
+    },
+    state: JobState,
+    resume: boolean,
+    playlist_id: string | null,
+}
+
+export interface PlaylistEntry {
+    index: number,
+    id: string | null,
+    url: string,
+    title: string | null,
+    duration: number | null,
+}
+
+export interface PlaylistInfo {
+    id: string,
+    title: string | null,
+    uploader: string | null,
+    entries: PlaylistEntry[],
+}
+
+export interface HistoryEntry {
+    id: number,
+    url: string,
+    options: Job["options"],
+    started_at: number,
+    finished_at: number | null,
+    state: JobState,
+    exit_code: number | null,
+    output_path: string | null,
+    file_size: number | null,
+}
+
+export interface MediaFormat {
+    format_id: string,
+    ext: string,
+    format_note: string | null,
+    resolution: string | null,
+    width: number | null,
+    height: number | null,
+    fps: number | null,
+    vcodec: string | null,
+    acodec: string | null,
+    tbr: number | null,
+    vbr: number | null,
+    abr: number | null,
+    filesize: number | null,
+    filesize_approx: number | null,
+}
+
+export interface MediaInfo {
+    id: string,
+    title: string,
+    uploader: string | null,
+    duration: number | null,
+    thumbnail: string | null,
+    extractor: string,
+    webpage_url: string | null,
+    formats: MediaFormat[],
+}